@@ -2,8 +2,8 @@
 // or to use with my own tools.
 
 use {
-    self::move_converter::MoveConverter,
-    anyhow::{Context, Result},
+    self::move_converter::{Calibration, Config, Format, GapPolicy, MoveConverter},
+    anyhow::{bail, Context, Result},
     std::{fs::File, io::BufReader, path::PathBuf},
     structopt::StructOpt,
 };
@@ -19,21 +19,77 @@ mod move_converter;
 /// found in ~/Library/Application Support/Suunto/Moveslink2 to a GPX file
 /// that can be uploaded to Strava.
 ///
+/// Pool swims don't have a track, so they're better off converted to TCX
+/// or FIT, which keep the lengths, stroke counts and styles.  Multisport
+/// moves become a single multisport TCX or FIT file, or one file per leg
+/// with --split (always, for GPX).
+///
 /// I also have another hacky program that extracts some statistics from my
 /// interval training, and the GPX file that this creates is compatible with
 /// that app, as well.
 struct Opt {
+    /// Output format: gpx, tcx, fit or csv
+    #[structopt(short, long, default_value)]
+    format: Format,
+    /// Pool length in meters for pool swims, if the watch's is wrong
+    #[structopt(long)]
+    pool_length: Option<f32>,
+    /// Print a summary of each converted move
+    #[structopt(short, long)]
+    summary: bool,
+    /// Write each leg of a multisport move to its own file
+    #[structopt(long)]
+    split: bool,
+    /// Look up the timezone from the move's position when the UTC offset
+    /// can't be worked out from the watch's local time (needs the
+    /// tz-lookup feature)
+    #[structopt(long)]
+    tz_lookup: bool,
+    /// What to do when the GPS signal is lost: drop, interpolate or
+    /// segment
+    #[structopt(long, default_value)]
+    gaps: GapPolicy,
+    /// Seconds after the last GPS fix before a sample counts as having
+    /// no position (not used with --gaps drop)
+    #[structopt(long, default_value = "10")]
+    max_fix_age: f64,
+    /// Multiply the watch's distance (and speed) by this, e.g., to fix
+    /// a badly calibrated foot pod
+    #[structopt(long)]
+    distance_factor: Option<f32>,
+    /// Compare the watch's distance with the GPS track's for moves
+    /// with a good fix, and suggest a --distance-factor
+    #[structopt(long)]
+    calibrate: bool,
     #[structopt(parse(from_os_str))]
     files: Vec<PathBuf>,
 }
 
 fn main() -> Result<()> {
     let opt = Opt::from_args();
+    if opt.tz_lookup && !cfg!(feature = "tz-lookup") {
+        bail!("--tz-lookup needs convert-moves to be built with --features tz-lookup");
+    }
+    let config = Config {
+        format: opt.format,
+        pool_length_m: opt.pool_length,
+        summary: opt.summary,
+        split: opt.split,
+        tz_lookup: opt.tz_lookup,
+        gaps: opt.gaps,
+        max_fix_age_sec: opt.max_fix_age,
+        distance_factor: opt.distance_factor,
+    };
+
+    let mut calibration = Calibration::default();
 
     for file in &opt.files {
         let input = File::open(file).with_context(|| format!("Failed to open {:?}", file))?;
         let converter = MoveConverter::new(BufReader::new(input));
-        converter.convert()?;
+        converter.convert(&config, &mut calibration)?;
+    }
+    if opt.calibrate {
+        calibration.report();
     }
     Ok(())
 }
@@ -1,11 +1,17 @@
 use {
-    self::sample_rec::{setter_for, SampleRec, TrkPt},
-    anyhow::Result,
-    chrono::NaiveDateTime,
+    self::{
+        multisport::{ActivityRec, SportChange},
+        sample_rec::{setter_for, SampleRec, TrkPt},
+        sport::Sport,
+        swim::{Swim, SwimRec},
+    },
+    anyhow::{bail, Result},
+    chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc},
     std::{
-        convert::TryInto,
+        fmt::{self, Display, Formatter},
         fs::File,
-        io::{BufWriter, Read},
+        io::{BufWriter, Read, Write},
+        str::FromStr,
     },
     xml::{
         common::XmlVersion,
@@ -15,9 +21,24 @@ use {
     },
 };
 
+mod csv;
+mod distance;
+mod fit;
+#[cfg(test)]
+mod fixture;
+mod gaps;
+mod gpx;
+mod multisport;
 mod sample_rec;
+mod sport;
+mod summary;
+mod swim;
+mod tcx;
+mod timezone;
+
+pub(crate) use self::{distance::Calibration, gaps::GapPolicy};
 
-type EventWriter = xml::writer::EventWriter<BufWriter<File>>;
+type EventWriter<W = BufWriter<File>> = xml::writer::EventWriter<W>;
 
 pub(crate) struct MoveConverter<R: Read> {
     reader: EventReader<R>,
@@ -25,6 +46,34 @@ pub(crate) struct MoveConverter<R: Read> {
 
 type WriteResult = std::result::Result<(), xml::writer::Error>;
 
+pub(crate) struct Config {
+    pub(crate) format: Format,
+    pub(crate) pool_length_m: Option<f32>,
+    pub(crate) summary: bool,
+    pub(crate) split: bool,
+    pub(crate) tz_lookup: bool,
+    pub(crate) gaps: GapPolicy,
+    pub(crate) max_fix_age_sec: f64,
+    pub(crate) distance_factor: Option<f32>,
+}
+
+// Without a pool length from either the watch or the command line,
+// we assume a short course pool.
+const DEFAULT_POOL_LENGTH_M: f32 = 25.0;
+
+// Everything we've sucked out of an .sml file that the writers need.
+// This is also used for each leg of a multisport move.
+pub(crate) struct Move {
+    local_time: NaiveDateTime,
+    start_utc: Option<DateTime<Utc>>,
+    end_utc: Option<DateTime<Utc>>,
+    utc_offset: Option<FixedOffset>,
+    sport: Sport,
+    points: Vec<TrkPt>,
+    swim: Option<Swim>,
+    sport_changes: Vec<SportChange>,
+}
+
 impl<R: Read> MoveConverter<R> {
     pub(crate) fn new(reader: R) -> Self {
         Self {
@@ -32,122 +81,386 @@ impl<R: Read> MoveConverter<R> {
         }
     }
 
-    pub(crate) fn convert(self) -> Result<()> {
+    pub(crate) fn convert(self, config: &Config, calibration: &mut Calibration) -> Result<()> {
+        let mut mv = self.parse(config)?;
+        // The calibration needs the watch's own distances, so it
+        // comes before any rescaling.
+        if mv.is_multisport() {
+            for leg in mv.legs() {
+                calibration.add(leg.filename(config.format), &leg);
+            }
+        } else {
+            calibration.add(mv.filename(config.format), &mv);
+        }
+        if let Some(factor) = config.distance_factor {
+            mv.rescale_distance(factor);
+        }
+        if !mv.is_multisport() {
+            return mv.write(config);
+        }
+
+        let legs = mv.legs();
+        // GPX (and CSV) has no way to say that one track is a swim
+        // and the next is a ride, so the legs always go in separate
+        // files.
+        if config.split || config.format == Format::Gpx || config.format == Format::Csv {
+            for leg in legs.iter().filter(|leg| leg.sport != Sport::Transition) {
+                leg.write(config)?;
+            }
+            return Ok(());
+        }
+
+        let filename = mv.filename(config.format);
+        let writer = BufWriter::new(File::create(&filename)?);
+        match config.format {
+            Format::Gpx | Format::Csv => unreachable!(),
+            Format::Tcx => tcx::write_multisport(&mv, &legs, xml_writer(writer))?,
+            Format::Fit => fit::write_multisport(&mv, &legs, writer)?,
+        }
+        if config.summary {
+            summary::print_multisport(&filename, &mv, &legs);
+        }
+        Ok(())
+    }
+
+    fn parse(self, config: &Config) -> Result<Move> {
         use XmlEvent::*;
 
         let mut sample: SampleRec = Default::default();
+        let mut swim_rec: SwimRec = Default::default();
+        let mut activity_rec: ActivityRec = Default::default();
+        let mut swim_events = Vec::new();
+        let mut sport_changes = Vec::new();
+        let mut in_events = false;
+        let mut event = None;
         let mut f = None;
+        let mut g = None;
+        let mut h = None;
         let mut dumped = false;
         let mut cadence_seen = false;
-        let mut writer = None;
+        let mut start_utc = None;
+        let mut last_fix_sec = None;
+        let mut points = Vec::new();
         for elem in self.reader {
             match elem? {
                 StartElement {
                     name: OwnedName { local_name, .. },
                     ..
-                } => f = setter_for(local_name),
+                } => match event {
+                    Some(Event::Swimming) => g = swim::setter_for(local_name),
+                    Some(Event::Activity) => h = multisport::setter_for(local_name),
+                    None => {
+                        if in_events && local_name == "Swimming" {
+                            event = Some(Event::Swimming);
+                            f = None;
+                        } else if in_events && local_name == "Activity" {
+                            event = Some(Event::Activity);
+                            f = None;
+                        } else {
+                            in_events |= local_name == "Events";
+                            f = setter_for(local_name);
+                        }
+                    }
+                },
                 EndElement {
                     name: OwnedName { local_name, .. },
                     ..
-                } if local_name == "Sample" && sample.is_periodic() => {
-                    if writer.is_none() {
-                        writer = Some(Self::writer_for_sample(&sample)?);
+                } if local_name == "Swimming" || local_name == "Activity" => {
+                    event = None;
+                    g = None;
+                    h = None;
+                }
+                EndElement {
+                    name: OwnedName { local_name, .. },
+                    ..
+                } if local_name == "Events" => in_events = false,
+                EndElement {
+                    name: OwnedName { local_name, .. },
+                    ..
+                } if local_name == "Sample" => {
+                    if start_utc.is_none() {
+                        start_utc = start_from_sample(&sample);
                     }
-                    if cadence_seen || !dumped {
-                        sample.dump(writer.as_mut().unwrap())?;
+                    if sample.take_position_update() {
+                        last_fix_sec = sample.elapsed();
+                    }
+                    if activity_rec.has_event() {
+                        sport_changes.extend(activity_rec.take_change(sample.elapsed()));
+                    } else if swim_rec.has_event() {
+                        swim_events.extend(swim_rec.take_event(sample.elapsed()));
+                    } else if sample.is_periodic() && (cadence_seen || !dumped) {
+                        match gaps::track_point(&sample, config, last_fix_sec) {
+                            Ok(pt) => points.push(pt),
+                            Err(e) => eprintln!("dropping {:?}: {:?}", sample, e),
+                        }
                         dumped = true;
                     }
                 }
-                Characters(value) => {
-                    if let Some(setter) = f {
-                        setter(&mut sample, value);
-                        if !cadence_seen && sample.has_cadence() {
-                            cadence_seen = true;
+                Characters(value) => match event {
+                    Some(Event::Swimming) => {
+                        if let Some(setter) = g {
+                            setter(&mut swim_rec, value);
                         }
                     }
-                }
+                    Some(Event::Activity) => {
+                        if let Some(setter) = h {
+                            setter(&mut activity_rec, value);
+                        }
+                    }
+                    None => {
+                        if let Some(setter) = f {
+                            setter(&mut sample, value);
+                            if !cadence_seen && sample.has_cadence() {
+                                cadence_seen = true;
+                            }
+                        }
+                    }
+                },
                 _ => {}
             }
         }
-        Self::write_postlude(writer.expect("no writer"))?;
-        Ok(())
-    }
 
-    fn writer_for_sample(sample: &SampleRec) -> Result<EventWriter> {
+        gaps::fill(&mut points, config.gaps);
+
         // To generate the proper name, we need to suck up the
         // characters from a DateTime tag, since that's the only
         // source of the local time.  Everything else is UTC.
-        let local_time: NaiveDateTime = sample.local_time.parse()?;
-        let filename = local_time
-            .format("Move_%Y_%m_%d_%H_%M_%S_Running.gpx")
-            .to_string();
+        let header_time: Option<NaiveDateTime> = if sample.local_time.is_empty() {
+            None
+        } else {
+            Some(sample.local_time.parse()?)
+        };
+        let first_utc = start_utc.or_else(|| points.first().map(|pt| pt.time_utc));
+        let mut utc_offset = match (header_time, first_utc) {
+            (Some(local), Some(utc)) => timezone::offset_from_header(local, utc),
+            _ => None,
+        };
+        if utc_offset.is_none() && config.tz_lookup {
+            let first_fix = points.iter().find_map(|pt| pt.position);
+            utc_offset = first_utc.zip(first_fix).and_then(|(utc, position)| {
+                timezone::offset_from_position(
+                    position.latitude_degrees,
+                    position.longitude_degrees,
+                    utc,
+                )
+            });
+        }
+        let local_time = match (header_time, first_utc, utc_offset) {
+            (Some(local), _, _) => local,
+            (None, Some(utc), Some(offset)) => utc.with_timezone(&offset).naive_local(),
+            _ => bail!("no DateTime in header"),
+        };
+
+        let swim = if swim_events.is_empty() {
+            None
+        } else {
+            let pool_length_m = config
+                .pool_length_m
+                .or_else(|| sample.pool_length())
+                .unwrap_or_else(|| {
+                    eprintln!(
+                        "no pool length recorded, assuming {} m",
+                        DEFAULT_POOL_LENGTH_M
+                    );
+                    DEFAULT_POOL_LENGTH_M
+                });
+            Some(Swim::from_events(&swim_events, pool_length_m))
+        };
+        // Everything used to be called running, so that's still what
+        // we call moves that don't say what they are.
+        let mut sport = match sample.header_activity_type() {
+            Some(id) => Sport::from_activity_type(id, swim.is_some()),
+            None if swim.is_some() => Sport::PoolSwimming,
+            None => Sport::Running,
+        };
+        // A single sport change is just the watch telling us what the
+        // sport is, not a multisport move.
+        if sport_changes.len() == 1 {
+            if let Some(leg) = sport_changes.pop() {
+                sport = leg.sport(swim.is_some()).unwrap_or(sport);
+            }
+        } else if !sport_changes.is_empty() && sport != Sport::Triathlon {
+            sport = Sport::Multisport;
+        }
+        Ok(Move {
+            local_time,
+            start_utc,
+            end_utc: None,
+            utc_offset,
+            sport,
+            points,
+            swim,
+            sport_changes,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Event {
+    Swimming,
+    Activity,
+}
+
+// A sample that has both a UTC time and an elapsed time tells us
+// when the move started.
+fn start_from_sample(sample: &SampleRec) -> Option<DateTime<Utc>> {
+    let utc = sample.utc()?;
+    let elapsed = sample.elapsed()?;
+    Some(utc - duration_from_sec(elapsed))
+}
+
+fn duration_from_sec(sec: f64) -> chrono::Duration {
+    chrono::Duration::milliseconds((sec * 1000.0).round() as i64)
+}
+
+fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+fn seconds_between(start: DateTime<Utc>, end: DateTime<Utc>) -> f64 {
+    (end - start).num_milliseconds() as f64 / 1000.0
+}
+
+impl Move {
+    fn write(&self, config: &Config) -> Result<()> {
+        let filename = self.filename(config.format);
         let writer = BufWriter::new(File::create(&filename)?);
-        let mut writer = EmitterConfig::new()
-            .line_separator("\r\n")
-            .perform_indent(true)
-            .create_writer(writer);
-        writer.write_prelude()?;
-        Ok(writer)
-    }
-
-    fn write_postlude(mut writer: EventWriter) -> Result<()> {
-        Self::close_trkseg(&mut writer)?;
-        Self::close_trk(&mut writer)?;
-        Self::close_gpx(&mut writer)?;
+        match config.format {
+            Format::Gpx => gpx::write(self, xml_writer(writer))?,
+            Format::Tcx => tcx::write(self, xml_writer(writer))?,
+            Format::Fit => fit::write(self, writer)?,
+            Format::Csv => csv::write(self, writer)?,
+        }
+        if config.summary {
+            summary::print(&filename, self);
+        }
         Ok(())
     }
 
-    fn close_trkseg(writer: &mut EventWriter) -> WriteResult {
-        writer.end_element()
+    fn filename(&self, format: Format) -> String {
+        self.local_time
+            .format(&format!(
+                "Move_%Y_%m_%d_%H_%M_%S_{}.{}",
+                self.sport,
+                format.extension()
+            ))
+            .to_string()
+    }
+
+    // Moves without any UTC times at all are pretty much hopeless,
+    // but pretending that the local time is UTC is better than
+    // giving up.
+    fn start_utc(&self) -> DateTime<Utc> {
+        self.start_utc
+            .or_else(|| self.points.first().map(|pt| pt.time_utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&self.local_time))
+    }
+
+    fn end_utc(&self) -> DateTime<Utc> {
+        let start = self.start_utc();
+        if let Some(end) = self.end_utc {
+            return end;
+        }
+        match &self.swim {
+            Some(swim) => self.utc_at(swim.intervals.last().map_or(0.0, |i| i.end_sec())),
+            None => self.points.last().map_or(start, |pt| pt.time_utc),
+        }
+    }
+
+    fn duration_sec(&self) -> f64 {
+        seconds_between(self.start_utc(), self.end_utc())
+    }
+
+    // The watch's distance is cumulative over the whole move, so a
+    // leg of a multisport move needs to subtract where it started.
+    fn distance_m(&self) -> f32 {
+        match &self.swim {
+            Some(swim) => swim.distance_m(),
+            None => match (self.points.first(), self.points.last()) {
+                (Some(first), Some(last)) => last.distance_m - first.distance_m,
+                _ => 0.0,
+            },
+        }
     }
 
-    fn close_trk(writer: &mut EventWriter) -> WriteResult {
-        writer.end_element()
+    fn local_start(&self) -> Option<DateTime<FixedOffset>> {
+        self.utc_offset
+            .map(|offset| self.start_utc().with_timezone(&offset))
     }
 
-    fn close_gpx(writer: &mut EventWriter) -> WriteResult {
-        writer.end_element()
+    fn utc_at(&self, elapsed_sec: f64) -> DateTime<Utc> {
+        self.start_utc() + duration_from_sec(elapsed_sec)
     }
 }
 
-trait DumpToGpx {
-    fn dump(&self, writer: &mut EventWriter) -> Result<()>;
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum Format {
+    #[default]
+    Gpx,
+    Tcx,
+    Fit,
+    Csv,
 }
 
-impl DumpToGpx for SampleRec {
-    fn dump(&self, writer: &mut EventWriter) -> Result<()> {
-        match TryInto::<TrkPt>::try_into(self) {
-            Ok(pt) => pt.dump(writer),
-            Err(e) => {
-                eprintln!("dropping {:?}: {:?}", self, e);
-                Ok(())
-            }
+impl Format {
+    fn extension(self) -> &'static str {
+        use Format::*;
+
+        match self {
+            Gpx => "gpx",
+            Tcx => "tcx",
+            Fit => "fit",
+            Csv => "csv",
         }
     }
 }
 
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.extension().fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseFormatError;
+
+impl Display for ParseFormatError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "must be gpx, tcx, fit or csv")
+    }
+}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Format::*;
+
+        match s.to_lowercase().as_str() {
+            "gpx" => Ok(Gpx),
+            "tcx" => Ok(Tcx),
+            "fit" => Ok(Fit),
+            "csv" => Ok(Csv),
+            _ => Err(ParseFormatError),
+        }
+    }
+}
+
+fn xml_writer<W: Write>(writer: W) -> EventWriter<W> {
+    EmitterConfig::new()
+        .line_separator("\r\n")
+        .perform_indent(true)
+        .create_writer(writer)
+}
+
 trait EventWriterExt {
-    fn write_prelude(&mut self) -> Result<()>;
     fn write_document_declaration(&mut self) -> WriteResult;
-    fn open_gpx(&mut self) -> WriteResult;
-    fn open_trk(&mut self) -> WriteResult;
-    fn name(&mut self, name: &str) -> WriteResult;
-    fn open_trkseg(&mut self) -> WriteResult;
     fn start_element(&mut self, element: &str) -> WriteResult;
     fn end_element(&mut self) -> WriteResult;
     fn dump_element<V: ToString>(&mut self, element: &str, value: V) -> WriteResult;
 }
 
-impl EventWriterExt for EventWriter {
-    fn write_prelude(&mut self) -> Result<()> {
-        self.write_document_declaration()?;
-        self.open_gpx()?;
-        self.open_trk()?;
-        self.name("Move")?;
-        self.open_trkseg()?;
-        Ok(())
-    }
-
+impl<W: Write> EventWriterExt for EventWriter<W> {
     fn write_document_declaration(&mut self) -> WriteResult {
         self.write(xml::writer::XmlEvent::StartDocument {
             standalone: Some(false),
@@ -156,32 +469,6 @@ impl EventWriterExt for EventWriter {
         })
     }
 
-    fn open_gpx(&mut self) -> WriteResult {
-        let gpx = xml::writer::XmlEvent::start_element("gpx")
-            .attr("version", "1.1")
-            .attr("creator", "Movescount - http://www.movescount.com")
-            .ns("xsi", "http://www.w3.org/2001/XMLSchema-instance")
-            .attr("xsi:schemaLocation", "http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd http://www.cluetrust.com/XML/GPXDATA/1/0 http://www.cluetrust.com/Schemas/gpxdata10.xsd http://www.garmin.com/xmlschemas/TrackPointExtension/v1 http://www.garmin.com/xmlschemas/TrackPointExtensionv1.xsd")
-            .ns("gpxdata", "http://www.cluetrust.com/XML/GPXDATA/1/0")
-            .ns("gpxtpx", "http://www.garmin.com/xmlschemas/TrackPointExtension/v1")
-            .default_ns("http://www.topografix.com/GPX/1/1");
-        self.write(gpx)
-    }
-
-    fn open_trk(&mut self) -> WriteResult {
-        self.start_element("trk")
-    }
-
-    fn name(&mut self, name: &str) -> WriteResult {
-        self.start_element("name")?;
-        self.write(xml::writer::XmlEvent::characters(name))?;
-        self.end_element()
-    }
-
-    fn open_trkseg(&mut self) -> WriteResult {
-        self.start_element("trkseg")
-    }
-
     fn start_element(&mut self, element: &str) -> WriteResult {
         self.write(xml::writer::XmlEvent::start_element(element))
     }
@@ -198,43 +485,135 @@ impl EventWriterExt for EventWriter {
     }
 }
 
-impl DumpToGpx for TrkPt {
-    fn dump(&self, writer: &mut EventWriter) -> Result<()> {
-        let lat = self.latitude();
-        let lon = self.longitude();
-        let trkpt = xml::writer::XmlEvent::start_element("trkpt")
-            .attr("lat", &lat)
-            .attr("lon", &lon);
-        writer.write(trkpt)?;
-        writer.dump_element("ele", self.altitude_m)?;
-        writer.dump_element("time", self.time())?;
-        writer.start_element("extensions")?;
-        writer.start_element("gpxtpx:TrackPointExtension")?;
-        if let Some(hr_bpm) = self.hr_bpm {
-            writer.dump_element("gpxtpx:hr", hr_bpm)?;
-        }
-        writer.end_element(/* gpxtpx */)?;
-
-        let has_cadence;
-        match self.cadence_ffm {
-            None => has_cadence = false,
-            Some(ffm) => {
-                has_cadence = true;
-                writer.dump_element("gpxdata:cadence", ffm)?;
-            }
+#[cfg(test)]
+mod tests {
+    use {
+        super::{swim::Style, *},
+        fitparser::profile::MesgNum,
+        std::{io::BufReader, path::Path},
+    };
+
+    fn config(format: Format, gaps: GapPolicy) -> Config {
+        Config {
+            format,
+            pool_length_m: None,
+            summary: false,
+            split: false,
+            tz_lookup: false,
+            gaps,
+            max_fix_age_sec: 10.0,
+            distance_factor: None,
         }
+    }
 
-        writer.dump_element("gpxdata:temp", self.temperature_c)?;
+    fn parse(fixture: &str, config: &Config) -> Move {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(fixture);
+        MoveConverter::new(BufReader::new(File::open(path).unwrap()))
+            .parse(config)
+            .unwrap()
+    }
 
-        if has_cadence {
-            writer.dump_element("gpxdata:distance", self.distance_m)?;
-            writer.dump_element("gpxdata:altitude", self.altitude_m)?;
-        }
+    #[test]
+    fn converts_a_pool_swim_without_utc_times() {
+        let mv = parse("pool_swim.sml", &config(Format::Fit, GapPolicy::Drop));
+        assert_eq!(mv.sport, Sport::PoolSwimming);
+        assert!(mv.points.is_empty());
+        // With nothing else to go on, the local time stands in for UTC.
+        assert_eq!(
+            mv.start_utc(),
+            Utc.with_ymd_and_hms(2019, 6, 1, 9, 30, 0).unwrap()
+        );
+        let swim = mv.swim.as_ref().unwrap();
+        assert_eq!(
+            swim.intervals
+                .iter()
+                .map(|interval| interval
+                    .lengths
+                    .iter()
+                    .map(|l| (l.start_sec, l.duration_sec, l.strokes, l.style))
+                    .collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![
+                    (0.0, 30.0, 3, Style::Freestyle),
+                    (30.0, 32.0, 2, Style::Freestyle)
+                ],
+                vec![
+                    (90.0, 30.0, 1, Style::Backstroke),
+                    (120.0, 30.0, 2, Style::Backstroke)
+                ],
+            ]
+        );
+        assert_eq!(mv.distance_m(), 100.0);
+        assert_eq!(mv.duration_sec(), 150.0);
+
+        let mut fit = Vec::new();
+        fit::write(&mv, &mut fit).unwrap();
+        let lengths = fitparser::from_bytes(&fit)
+            .unwrap()
+            .into_iter()
+            .filter(|record| record.kind() == MesgNum::Length)
+            .filter_map(|record| {
+                record
+                    .fields()
+                    .iter()
+                    .find(|field| field.name() == "length_type")
+                    .map(|field| field.value().to_string())
+            })
+            .collect::<Vec<_>>();
+        // With the rest between the intervals as an idle length.
+        assert_eq!(lengths, ["active", "active", "idle", "active", "active"]);
+
+        let mut tcx = Vec::new();
+        tcx::write(&mv, xml_writer(&mut tcx)).unwrap();
+        let tcx = String::from_utf8(tcx).unwrap();
+        assert_eq!(tcx.matches("<Lap ").count(), 3);
+        assert!(tcx.contains("<Notes>2 x 25 m freestyle, 5 strokes</Notes>"));
+        assert!(tcx.contains("<Notes>2 x 25 m backstroke, 3 strokes</Notes>"));
+    }
+
+    #[test]
+    fn converts_a_multisport_move_with_a_gap() {
+        let mv = parse("run_ride.sml", &config(Format::Tcx, GapPolicy::Segment));
+        assert_eq!(mv.sport, Sport::Multisport);
+        assert_eq!(
+            mv.start_utc(),
+            Utc.with_ymd_and_hms(2019, 6, 1, 7, 30, 0).unwrap()
+        );
+        assert_eq!(mv.utc_offset, FixedOffset::east_opt(2 * 60 * 60));
+        // The fix from 10 s is still fresh at 20 s, but not after.
+        assert_eq!(
+            mv.points
+                .iter()
+                .map(|pt| (pt.position.is_some(), pt.new_segment))
+                .collect::<Vec<_>>(),
+            vec![
+                (true, false),
+                (true, false),
+                (true, false),
+                (false, false),
+                (false, false),
+                (true, true),
+                (true, false),
+                (true, false),
+            ]
+        );
+
+        let legs = mv.legs();
+        assert_eq!(
+            legs.iter()
+                .map(|leg| (leg.sport, leg.points.len(), leg.distance_m()))
+                .collect::<Vec<_>>(),
+            vec![(Sport::Running, 6, 150.0), (Sport::Cycling, 2, 220.0)]
+        );
 
-        writer.dump_element("gpxdata:seaLevelPressure", self.sea_level_pressure_millibar)?;
-        writer.dump_element("gpxdata:speed", self.speed_mps)?;
-        writer.dump_element("gpxdata:verticalSpeed", self.vertical_speed_mps)?;
-        writer.end_element(/* extensions */)?;
-        writer.end_element().map_err(|e| e.into())
+        let mut tcx = Vec::new();
+        tcx::write_multisport(&mv, &legs, xml_writer(&mut tcx)).unwrap();
+        let tcx = String::from_utf8(tcx).unwrap();
+        assert!(tcx.contains("<Activity Sport=\"Running\">"));
+        assert!(tcx.contains("<Activity Sport=\"Biking\">"));
+        assert_eq!(tcx.matches("<Trackpoint>").count(), 8);
     }
 }
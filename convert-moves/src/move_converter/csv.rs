@@ -0,0 +1,62 @@
+use {
+    super::{sample_rec::TrkPt, Move},
+    anyhow::Result,
+    std::io::Write,
+};
+
+// One row per track point, for poking at with a spreadsheet or my own
+// tools.  Points without a GPS fix have empty latitude and longitude,
+// and the segment number goes up after each gap.
+
+pub(super) fn write<W: Write>(mv: &Move, mut writer: W) -> Result<()> {
+    if mv.swim.is_some() {
+        eprintln!("pool swims have no track; use --format tcx or fit to keep the lengths");
+    }
+    writeln!(
+        writer,
+        "time,segment,latitude,longitude,altitude_m,distance_m,hr_bpm,cadence,speed_mps,vertical_speed_mps,temperature_c,sea_level_pressure_mbar"
+    )?;
+    let mut segment = 1;
+    for pt in &mv.points {
+        if pt.new_segment {
+            segment += 1;
+        }
+        pt.write_row(segment, &mut writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+trait WriteToCsv {
+    fn write_row<W: Write>(&self, segment: u32, writer: &mut W) -> Result<()>;
+}
+
+impl WriteToCsv for TrkPt {
+    fn write_row<W: Write>(&self, segment: u32, writer: &mut W) -> Result<()> {
+        let (lat, lon) = match &self.position {
+            Some(position) => (position.latitude(), position.longitude()),
+            None => Default::default(),
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.time(),
+            segment,
+            lat,
+            lon,
+            self.altitude_m,
+            self.distance_m,
+            optional(self.hr_bpm),
+            optional(self.cadence_ffm),
+            self.speed_mps,
+            self.vertical_speed_mps,
+            self.temperature_c,
+            self.sea_level_pressure_millibar
+        )?;
+        Ok(())
+    }
+}
+
+fn optional(value: Option<u16>) -> String {
+    value.map_or_else(String::new, |v| v.to_string())
+}
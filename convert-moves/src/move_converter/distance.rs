@@ -0,0 +1,196 @@
+use super::{sample_rec::Position, sport::Sport, Move};
+
+// The watch's distance comes from the foot pod (or the accelerometer)
+// when it has one, and the foot pod is only as good as its
+// calibration.  Comparing it with the distance along the GPS track of
+// moves that had a good fix the whole way tells us how far off it is.
+
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+// A move only helps with the calibration if the GPS had a fix for
+// nearly all of it, and it's long enough that GPS jitter doesn't
+// matter much.
+const MIN_FIX_FRACTION: f64 = 0.9;
+const MIN_CALIBRATION_DISTANCE_M: f64 = 1000.0;
+
+pub(crate) fn haversine_m(from: Position, to: Position) -> f64 {
+    let (lat1, lon1) = (
+        f64::from(from.latitude_degrees).to_radians(),
+        f64::from(from.longitude_degrees).to_radians(),
+    );
+    let (lat2, lon2) = (
+        f64::from(to.latitude_degrees).to_radians(),
+        f64::from(to.longitude_degrees).to_radians(),
+    );
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+impl Move {
+    // The distance along the GPS track.  We don't count the straight
+    // line across a gap that starts a new segment.
+    pub(crate) fn track_distance_m(&self) -> Option<f64> {
+        let mut previous: Option<Position> = None;
+        let mut total = None;
+        for pt in &self.points {
+            let position = match pt.position {
+                Some(position) => position,
+                None => continue,
+            };
+            if let (Some(from), false) = (previous, pt.new_segment) {
+                *total.get_or_insert(0.0) += haversine_m(from, position);
+            }
+            previous = Some(position);
+        }
+        total
+    }
+
+    fn fix_fraction(&self) -> f64 {
+        if self.points.is_empty() {
+            return 0.0;
+        }
+        let fixes = self
+            .points
+            .iter()
+            .filter(|pt| pt.position.is_some())
+            .count();
+        fixes as f64 / self.points.len() as f64
+    }
+
+    // For when the foot pod was known to be off.  The speed comes from
+    // the same place as the distance, so it gets the same treatment.
+    pub(crate) fn rescale_distance(&mut self, factor: f32) {
+        for pt in &mut self.points {
+            pt.distance_m *= factor;
+            pt.speed_mps *= factor;
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Calibration {
+    moves: Vec<CalibrationMove>,
+}
+
+struct CalibrationMove {
+    name: String,
+    watch_m: f64,
+    track_m: f64,
+}
+
+impl Calibration {
+    // Only sports on foot, since that's where a foot pod is used.
+    pub(crate) fn add(&mut self, name: String, mv: &Move) {
+        let on_foot = matches!(mv.sport, Sport::Running | Sport::Walking | Sport::Trekking);
+        if !on_foot || mv.fix_fraction() < MIN_FIX_FRACTION {
+            return;
+        }
+        let track_m = match mv.track_distance_m() {
+            Some(track_m) if track_m >= MIN_CALIBRATION_DISTANCE_M => track_m,
+            _ => return,
+        };
+        let watch_m = f64::from(mv.distance_m());
+        if watch_m > 0.0 {
+            self.moves.push(CalibrationMove {
+                name,
+                watch_m,
+                track_m,
+            });
+        }
+    }
+
+    pub(crate) fn report(&self) {
+        println!("foot pod calibration");
+        if self.moves.is_empty() {
+            println!("  no moves with enough GPS to calibrate against");
+            return;
+        }
+        for m in &self.moves {
+            println!(
+                "  {}: watch {:.0} m, GPS {:.0} m, factor {:.4}",
+                m.name,
+                m.watch_m,
+                m.track_m,
+                m.track_m / m.watch_m
+            );
+        }
+        println!(
+            "  {} moves, suggested --distance-factor {:.4}",
+            self.moves.len(),
+            self.factor()
+        );
+    }
+
+    // Weighting by distance keeps a short jog around the block from
+    // counting as much as a long run.
+    fn factor(&self) -> f64 {
+        let watch_m = self.moves.iter().map(|m| m.watch_m).sum::<f64>();
+        let track_m = self.moves.iter().map(|m| m.track_m).sum::<f64>();
+        track_m / watch_m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::fixture, *};
+
+    fn position(latitude_degrees: f32, longitude_degrees: f32) -> Position {
+        Position {
+            latitude_degrees,
+            longitude_degrees,
+        }
+    }
+
+    #[test]
+    fn a_degree_is_about_111_km() {
+        let degree_m = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+        // Along a meridian, and along the equator.
+        assert!((haversine_m(position(0.0, 0.0), position(1.0, 0.0)) - degree_m).abs() < 0.01);
+        assert!((haversine_m(position(0.0, 0.0), position(0.0, 1.0)) - degree_m).abs() < 0.01);
+        // The Eiffel Tower to Big Ben is about 341 km.
+        let paris_london = haversine_m(position(48.8584, 2.2945), position(51.5007, -0.1246));
+        assert!(
+            (paris_london / 1000.0 - 341.0).abs() < 1.0,
+            "{}",
+            paris_london
+        );
+    }
+
+    // 11 points heading north 0.001° (111 m) at a time, for which the
+    // watch says 1000 m.
+    fn run(sport: Sport, fixes: usize) -> Move {
+        let points = (0..11)
+            .map(|i| {
+                let position = Some((46.0 + i as f32 * 0.001, -121.0)).filter(|_| i < fixes);
+                fixture::point(i as i64 * 60, position, i as f32 * 100.0)
+            })
+            .collect();
+        fixture::track_move(sport, points)
+    }
+
+    #[test]
+    fn the_track_is_compared_with_the_watch() {
+        let mv = run(Sport::Running, 11);
+        assert!((mv.track_distance_m().unwrap() - 1111.95).abs() < 1.0);
+
+        let mut calibration = Calibration::default();
+        calibration.add("run".to_string(), &mv);
+        // Not on foot, too little GPS, and too short.
+        calibration.add("ride".to_string(), &run(Sport::Cycling, 11));
+        calibration.add("tunnel".to_string(), &run(Sport::Running, 9));
+        let mut short = run(Sport::Running, 11);
+        short.points.truncate(5);
+        calibration.add("short".to_string(), &short);
+        assert_eq!(calibration.moves.len(), 1);
+        assert!((calibration.factor() - 1.112).abs() < 0.001);
+    }
+
+    #[test]
+    fn rescaling_changes_distance_and_speed() {
+        let mut mv = run(Sport::Running, 11);
+        mv.rescale_distance(1.5);
+        assert_eq!(mv.distance_m(), 1500.0);
+        assert_eq!(mv.points[1].speed_mps, 4.5);
+    }
+}
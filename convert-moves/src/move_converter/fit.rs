@@ -0,0 +1,628 @@
+use {
+    super::{sample_rec::TrkPt, swim::Swim, Move},
+    anyhow::Result,
+    chrono::{DateTime, Utc},
+    std::io::Write,
+};
+
+// A minimal FIT encoder.  We only write the handful of messages that
+// Strava and friends need in order to make sense of an activity:
+// file_id, record, length, lap, session and activity.  The global
+// message and field numbers come from the FIT SDK's Profile.xlsx.
+
+const FILE_ID: u16 = 0;
+const SESSION: u16 = 18;
+const LAP: u16 = 19;
+const RECORD: u16 = 20;
+const ACTIVITY: u16 = 34;
+const LENGTH: u16 = 101;
+
+const TIMESTAMP: u8 = 253;
+const MESSAGE_INDEX: u8 = 254;
+
+// event and event_type enums
+const EVENT_SESSION: u8 = 8;
+const EVENT_LAP: u8 = 9;
+const EVENT_ACTIVITY: u8 = 26;
+const EVENT_LENGTH: u8 = 28;
+const EVENT_TYPE_STOP: u8 = 1;
+
+const MANUFACTURER_SUUNTO: u16 = 23;
+const FILE_TYPE_ACTIVITY: u8 = 4;
+
+// FIT time starts at 1989-12-31T00:00:00Z
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+
+pub(super) fn write<W: Write>(mv: &Move, writer: W) -> Result<()> {
+    let mut fit = FitEncoder::default();
+    encode_file_id(mv, &mut fit);
+    encode_session(mv, &mut fit, 0, 0);
+    encode_activity(mv, &mut fit, 1);
+    finish(fit, writer)
+}
+
+// A multisport move is a single activity with one session per leg,
+// including the transitions.
+pub(super) fn write_multisport<W: Write>(mv: &Move, legs: &[Move], writer: W) -> Result<()> {
+    let mut fit = FitEncoder::default();
+    encode_file_id(mv, &mut fit);
+    let mut first_lap_index = 0;
+    for (i, leg) in legs.iter().enumerate() {
+        first_lap_index += encode_session(leg, &mut fit, i as u16, first_lap_index);
+    }
+    encode_activity(mv, &mut fit, legs.len() as u16);
+    finish(fit, writer)
+}
+
+fn finish<W: Write>(fit: FitEncoder, mut writer: W) -> Result<()> {
+    writer.write_all(&fit.finish())?;
+    Ok(())
+}
+
+fn encode_file_id(mv: &Move, fit: &mut FitEncoder) {
+    fit.message(
+        FILE_ID,
+        vec![
+            (0, Value::Enum(FILE_TYPE_ACTIVITY)),
+            (1, Value::UInt16(MANUFACTURER_SUUNTO)),
+            (4, Value::UInt32(fit_time(mv.start_utc()))),
+        ],
+    );
+}
+
+// Writes the records, laps and session for a move (or a leg of a
+// multisport move) and returns the number of laps.
+fn encode_session(mv: &Move, fit: &mut FitEncoder, index: u16, first_lap_index: u16) -> u16 {
+    for pt in &mv.points {
+        pt.encode(fit);
+    }
+    let num_laps = match &mv.swim {
+        Some(swim) => encode_swim(mv, swim, fit, first_lap_index),
+        None => {
+            encode_track_lap(mv, fit, first_lap_index);
+            1
+        }
+    };
+
+    let (sport, sub_sport) = mv.sport.fit_sport();
+    let elapsed = scaled(mv.duration_sec(), 1000.0);
+    let mut session = vec![
+        (TIMESTAMP, Value::UInt32(fit_time(mv.end_utc()))),
+        (MESSAGE_INDEX, Value::UInt16(index)),
+        (0, Value::Enum(EVENT_SESSION)),
+        (1, Value::Enum(EVENT_TYPE_STOP)),
+        (2, Value::UInt32(fit_time(mv.start_utc()))),
+        (5, Value::Enum(sport)),
+        (6, Value::Enum(sub_sport)),
+        (7, Value::UInt32(elapsed)),
+        (8, Value::UInt32(elapsed)),
+        (9, Value::UInt32(scaled(mv.distance_m().into(), 100.0))),
+        (25, Value::UInt16(first_lap_index)),
+        (26, Value::UInt16(num_laps)),
+    ];
+    if let Some(swim) = &mv.swim {
+        session.extend(vec![
+            (10, Value::UInt32(swim.strokes())),
+            (33, Value::UInt16(swim.num_lengths() as u16)),
+            (
+                44,
+                Value::UInt16(scaled(swim.pool_length_m.into(), 100.0) as u16),
+            ),
+            (46, Value::Enum(0 /* metric */)),
+            (47, Value::UInt16(swim.num_lengths() as u16)),
+        ]);
+    }
+    fit.message(SESSION, session);
+    num_laps
+}
+
+fn encode_activity(mv: &Move, fit: &mut FitEncoder, num_sessions: u16) {
+    let end = fit_time(mv.end_utc());
+    let mut activity = vec![
+        (TIMESTAMP, Value::UInt32(end)),
+        (0, Value::UInt32(scaled(mv.duration_sec(), 1000.0))),
+        (1, Value::UInt16(num_sessions)),
+        (2, Value::Enum(0 /* manual */)),
+        (3, Value::Enum(EVENT_ACTIVITY)),
+        (4, Value::Enum(EVENT_TYPE_STOP)),
+    ];
+    if let Some(offset) = mv.utc_offset {
+        let local = i64::from(end) + i64::from(offset.local_minus_utc());
+        activity.push((5, Value::UInt32(local as u32)));
+    }
+    fit.message(ACTIVITY, activity);
+}
+
+fn encode_track_lap(mv: &Move, fit: &mut FitEncoder, lap_index: u16) {
+    let (sport, sub_sport) = mv.sport.fit_sport();
+    let elapsed = scaled(mv.duration_sec(), 1000.0);
+    fit.message(
+        LAP,
+        vec![
+            (TIMESTAMP, Value::UInt32(fit_time(mv.end_utc()))),
+            (MESSAGE_INDEX, Value::UInt16(lap_index)),
+            (0, Value::Enum(EVENT_LAP)),
+            (1, Value::Enum(EVENT_TYPE_STOP)),
+            (2, Value::UInt32(fit_time(mv.start_utc()))),
+            (7, Value::UInt32(elapsed)),
+            (8, Value::UInt32(elapsed)),
+            (9, Value::UInt32(scaled(mv.distance_m().into(), 100.0))),
+            (25, Value::Enum(sport)),
+            (39, Value::Enum(sub_sport)),
+        ],
+    );
+}
+
+// Each interval is a lap made up of active lengths.  Each rest is a
+// lap with a single idle length, which is how Garmin's own watches
+// record them.  Returns the number of laps.
+fn encode_swim(mv: &Move, swim: &Swim, fit: &mut FitEncoder, first_lap_index: u16) -> u16 {
+    let (sport, sub_sport) = mv.sport.fit_sport();
+    let mut length_index = 0u16;
+    let mut lap_index = first_lap_index;
+    let mut previous_end = None;
+
+    for interval in &swim.intervals {
+        if let Some(rest_start) = previous_end {
+            let rest_sec = interval.start_sec() - rest_start;
+            let elapsed = scaled(rest_sec, 1000.0);
+            let start = fit_time(mv.utc_at(rest_start));
+            let end = fit_time(mv.utc_at(interval.start_sec()));
+            fit.message(
+                LENGTH,
+                vec![
+                    (TIMESTAMP, Value::UInt32(end)),
+                    (MESSAGE_INDEX, Value::UInt16(length_index)),
+                    (0, Value::Enum(EVENT_LENGTH)),
+                    (1, Value::Enum(EVENT_TYPE_STOP)),
+                    (2, Value::UInt32(start)),
+                    (3, Value::UInt32(elapsed)),
+                    (4, Value::UInt32(elapsed)),
+                    (12, Value::Enum(0 /* idle */)),
+                ],
+            );
+            fit.message(
+                LAP,
+                vec![
+                    (TIMESTAMP, Value::UInt32(end)),
+                    (MESSAGE_INDEX, Value::UInt16(lap_index)),
+                    (0, Value::Enum(EVENT_LAP)),
+                    (1, Value::Enum(EVENT_TYPE_STOP)),
+                    (2, Value::UInt32(start)),
+                    (7, Value::UInt32(elapsed)),
+                    (8, Value::UInt32(elapsed)),
+                    (9, Value::UInt32(0)),
+                    (25, Value::Enum(sport)),
+                    (32, Value::UInt16(1)),
+                    (35, Value::UInt16(length_index)),
+                    (39, Value::Enum(sub_sport)),
+                    (40, Value::UInt16(0)),
+                ],
+            );
+            length_index += 1;
+            lap_index += 1;
+        }
+
+        let first_length_index = length_index;
+        for length in &interval.lengths {
+            let elapsed = scaled(length.duration_sec, 1000.0);
+            let speed = if length.duration_sec > 0.0 {
+                f64::from(swim.pool_length_m) / length.duration_sec
+            } else {
+                0.0
+            };
+            let cadence = if length.duration_sec > 0.0 {
+                (f64::from(length.strokes) * 60.0 / length.duration_sec).round() as u8
+            } else {
+                0
+            };
+            fit.message(
+                LENGTH,
+                vec![
+                    (
+                        TIMESTAMP,
+                        Value::UInt32(fit_time(mv.utc_at(length.start_sec + length.duration_sec))),
+                    ),
+                    (MESSAGE_INDEX, Value::UInt16(length_index)),
+                    (0, Value::Enum(EVENT_LENGTH)),
+                    (1, Value::Enum(EVENT_TYPE_STOP)),
+                    (2, Value::UInt32(fit_time(mv.utc_at(length.start_sec)))),
+                    (3, Value::UInt32(elapsed)),
+                    (4, Value::UInt32(elapsed)),
+                    (5, Value::UInt16(length.strokes)),
+                    (6, Value::UInt16(scaled(speed, 1000.0) as u16)),
+                    (7, Value::Enum(length.style.fit_swim_stroke())),
+                    (9, Value::UInt8(cadence)),
+                    (12, Value::Enum(1 /* active */)),
+                ],
+            );
+            length_index += 1;
+        }
+
+        let elapsed = scaled(interval.duration_sec(), 1000.0);
+        let num_lengths = interval.lengths.len() as u16;
+        fit.message(
+            LAP,
+            vec![
+                (
+                    TIMESTAMP,
+                    Value::UInt32(fit_time(mv.utc_at(interval.end_sec()))),
+                ),
+                (MESSAGE_INDEX, Value::UInt16(lap_index)),
+                (0, Value::Enum(EVENT_LAP)),
+                (1, Value::Enum(EVENT_TYPE_STOP)),
+                (2, Value::UInt32(fit_time(mv.utc_at(interval.start_sec())))),
+                (7, Value::UInt32(elapsed)),
+                (8, Value::UInt32(elapsed)),
+                (
+                    9,
+                    Value::UInt32(scaled(
+                        f64::from(num_lengths) * f64::from(swim.pool_length_m),
+                        100.0,
+                    )),
+                ),
+                (10, Value::UInt32(interval.strokes())),
+                (25, Value::Enum(sport)),
+                (32, Value::UInt16(num_lengths)),
+                (35, Value::UInt16(first_length_index)),
+                (38, Value::Enum(interval.style().fit_swim_stroke())),
+                (39, Value::Enum(sub_sport)),
+                (40, Value::UInt16(num_lengths)),
+            ],
+        );
+        lap_index += 1;
+        previous_end = Some(interval.end_sec());
+    }
+    lap_index - first_lap_index
+}
+
+trait EncodeToFit {
+    fn encode(&self, fit: &mut FitEncoder);
+}
+
+impl EncodeToFit for TrkPt {
+    fn encode(&self, fit: &mut FitEncoder) {
+        let mut fields = vec![(TIMESTAMP, Value::UInt32(fit_time(self.time_utc)))];
+        // Records without a fix just leave the position out.
+        if let Some(position) = self.position {
+            fields.push((0, Value::SInt32(semicircles(position.latitude_degrees))));
+            fields.push((1, Value::SInt32(semicircles(position.longitude_degrees))));
+        }
+        fields.extend(vec![
+            (
+                2,
+                Value::UInt16(scaled((self.altitude_m + 500.0).into(), 5.0) as u16),
+            ),
+            (5, Value::UInt32(scaled(self.distance_m.into(), 100.0))),
+            (
+                6,
+                Value::UInt16(scaled(self.speed_mps.into(), 1000.0) as u16),
+            ),
+            (13, Value::SInt8(self.temperature_c.round() as i8)),
+            (
+                32,
+                Value::SInt16((self.vertical_speed_mps * 1000.0).round() as i16),
+            ),
+        ]);
+        if let Some(hr_bpm) = self.hr_bpm {
+            fields.push((3, Value::UInt8(hr_bpm.min(254) as u8)));
+        }
+        if let Some(ffm) = self.cadence_ffm {
+            fields.push((4, Value::UInt8(ffm.min(254) as u8)));
+        }
+        fit.message(RECORD, fields);
+    }
+}
+
+fn fit_time(time: DateTime<Utc>) -> u32 {
+    (time.timestamp() - FIT_EPOCH_OFFSET) as u32
+}
+
+fn semicircles(degrees: f32) -> i32 {
+    (f64::from(degrees) * (f64::from(1u32 << 31) / 180.0)).round() as i32
+}
+
+fn scaled(value: f64, scale: f64) -> u32 {
+    (value * scale).round().max(0.0) as u32
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Value {
+    Enum(u8),
+    SInt8(i8),
+    UInt8(u8),
+    SInt16(i16),
+    UInt16(u16),
+    SInt32(i32),
+    UInt32(u32),
+}
+
+impl Value {
+    fn base_type(self) -> u8 {
+        use Value::*;
+
+        match self {
+            Enum(_) => 0x00,
+            SInt8(_) => 0x01,
+            UInt8(_) => 0x02,
+            SInt16(_) => 0x83,
+            UInt16(_) => 0x84,
+            SInt32(_) => 0x85,
+            UInt32(_) => 0x86,
+        }
+    }
+
+    fn size(self) -> u8 {
+        use Value::*;
+
+        match self {
+            Enum(_) | SInt8(_) | UInt8(_) => 1,
+            SInt16(_) | UInt16(_) => 2,
+            SInt32(_) | UInt32(_) => 4,
+        }
+    }
+
+    fn encode(self, buf: &mut Vec<u8>) {
+        use Value::*;
+
+        match self {
+            Enum(v) | UInt8(v) => buf.push(v),
+            SInt8(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            SInt16(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            UInt16(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            SInt32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+            UInt32(v) => buf.extend_from_slice(&v.to_le_bytes()),
+        }
+    }
+}
+
+// global message number and (field number, size, base type)
+type Definition = (u16, Vec<(u8, u8, u8)>);
+
+const LOCAL_MESSAGE_TYPES: usize = 16;
+
+#[derive(Default)]
+struct FitEncoder {
+    data: Vec<u8>,
+    definitions: Vec<Definition>,
+    next_victim: usize,
+}
+
+impl FitEncoder {
+    // Writes a definition message whenever we see a layout that
+    // isn't already associated with one of the sixteen local message
+    // types, then the data message itself.
+    fn message(&mut self, global: u16, fields: Vec<(u8, Value)>) {
+        let definition = (
+            global,
+            fields
+                .iter()
+                .map(|(num, v)| (*num, v.size(), v.base_type()))
+                .collect::<Vec<_>>(),
+        );
+        let local = match self.definitions.iter().position(|d| *d == definition) {
+            Some(local) => local,
+            None => {
+                let local = if self.definitions.len() < LOCAL_MESSAGE_TYPES {
+                    self.definitions.push(definition.clone());
+                    self.definitions.len() - 1
+                } else {
+                    let local = self.next_victim;
+                    self.next_victim = (self.next_victim + 1) % LOCAL_MESSAGE_TYPES;
+                    self.definitions[local] = definition.clone();
+                    local
+                };
+                self.data.push(0x40 | local as u8);
+                self.data.push(0); // reserved
+                self.data.push(0); // little endian
+                self.data.extend_from_slice(&global.to_le_bytes());
+                self.data.push(definition.1.len() as u8);
+                for (num, size, base_type) in &definition.1 {
+                    self.data.extend_from_slice(&[*num, *size, *base_type]);
+                }
+                local
+            }
+        };
+        self.data.push(local as u8);
+        for (_, value) in fields {
+            value.encode(&mut self.data);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut file = Vec::with_capacity(14 + self.data.len() + 2);
+        file.push(14); // header size
+        file.push(0x10); // protocol version 1.0
+        file.extend_from_slice(&2093u16.to_le_bytes()); // profile version 20.93
+        file.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        let header_crc = crc(&file);
+        file.extend_from_slice(&header_crc.to_le_bytes());
+        file.extend_from_slice(&self.data);
+        let file_crc = crc(&file);
+        file.extend_from_slice(&file_crc.to_le_bytes());
+        file
+    }
+}
+
+fn crc(bytes: &[u8]) -> u16 {
+    const CRC_TABLE: [u16; 16] = [
+        0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800,
+        0xB401, 0x5000, 0x9C01, 0x8801, 0x4400,
+    ];
+
+    bytes.iter().fold(0, |mut crc, byte| {
+        let tmp = CRC_TABLE[usize::from(crc & 0xF)];
+        crc = (crc >> 4) & 0x0FFF;
+        crc = crc ^ tmp ^ CRC_TABLE[usize::from(byte & 0xF)];
+        let tmp = CRC_TABLE[usize::from(crc & 0xF)];
+        crc = (crc >> 4) & 0x0FFF;
+        crc ^ tmp ^ CRC_TABLE[usize::from((byte >> 4) & 0xF)]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::move_converter::{fixture, sport::Sport},
+        fitparser::{profile::MesgNum, FitDataRecord},
+        std::{fs, path::Path},
+    };
+
+    fn encode(mv: &Move) -> Vec<u8> {
+        let mut fit = Vec::new();
+        write(mv, &mut fit).unwrap();
+        fit
+    }
+
+    fn messages(fit: &[u8], kind: MesgNum) -> Vec<FitDataRecord> {
+        fitparser::from_bytes(fit)
+            .unwrap()
+            .into_iter()
+            .filter(|record| record.kind() == kind)
+            .collect()
+    }
+
+    fn field(record: &FitDataRecord, name: &str) -> Option<String> {
+        record
+            .fields()
+            .iter()
+            .find(|f| f.name() == name)
+            .map(|f| f.value().to_string())
+    }
+
+    #[test]
+    fn crc_is_crc_16_arc() {
+        assert_eq!(crc(b"123456789"), 0xBB3D);
+    }
+
+    // An example file from the FIT SDK, with a 14-byte header.
+    #[test]
+    fn crc_matches_the_sdks() {
+        let sdk = fs::read(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/WeightScaleSingleUser.fit"),
+        )
+        .unwrap();
+        assert_eq!(crc(&sdk[..12]), u16::from_le_bytes([sdk[12], sdk[13]]));
+        // Which makes the CRC over the whole file, including its own
+        // CRC, zero.
+        assert_eq!(crc(&sdk), 0);
+    }
+
+    #[test]
+    fn header_and_crc() {
+        let fit = encode(&fixture::track_move(
+            Sport::Running,
+            vec![fixture::point(0, Some((46.85, -121.76)), 0.0)],
+        ));
+        assert_eq!(fit[0], 14);
+        assert_eq!(&fit[8..12], b".FIT");
+        let data_size = u32::from_le_bytes([fit[4], fit[5], fit[6], fit[7]]) as usize;
+        assert_eq!(fit.len(), 14 + data_size + 2);
+        assert_eq!(crc(&fit[..12]), u16::from_le_bytes([fit[12], fit[13]]));
+        assert_eq!(crc(&fit), 0);
+    }
+
+    #[test]
+    fn records_and_a_lap() {
+        let fit = encode(&fixture::track_move(
+            Sport::Running,
+            vec![
+                fixture::point(0, Some((46.85, -121.76)), 0.0),
+                // Without a fix.
+                fixture::point(10, None, 30.0),
+            ],
+        ));
+
+        let records = messages(&fit, MesgNum::Record);
+        assert_eq!(records.len(), 2);
+        let latitude: i64 = field(&records[0], "position_lat").unwrap().parse().unwrap();
+        assert_eq!(latitude, i64::from(semicircles(46.85)));
+        assert_eq!(field(&records[0], "heart_rate").as_deref(), Some("140"));
+        assert_eq!(field(&records[0], "cadence").as_deref(), Some("85"));
+        assert_eq!(field(&records[1], "position_lat"), None);
+        assert_eq!(field(&records[1], "distance").as_deref(), Some("30"));
+        assert_eq!(
+            field(&records[1], "timestamp").as_deref(),
+            Some("2019-06-01 07:30:10 +00:00")
+        );
+
+        let laps = messages(&fit, MesgNum::Lap);
+        assert_eq!(laps.len(), 1);
+        assert_eq!(field(&laps[0], "sport").as_deref(), Some("running"));
+        assert_eq!(field(&laps[0], "total_elapsed_time").as_deref(), Some("10"));
+        assert_eq!(field(&laps[0], "total_distance").as_deref(), Some("30"));
+
+        let sessions = messages(&fit, MesgNum::Session);
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(field(&sessions[0], "num_laps").as_deref(), Some("1"));
+        let activities = messages(&fit, MesgNum::Activity);
+        assert_eq!(
+            field(&activities[0], "local_timestamp").as_deref(),
+            Some("2019-06-01 09:30:10 +00:00")
+        );
+    }
+
+    // A rest between intervals is a lap of its own, with a single idle
+    // length.
+    #[test]
+    fn lengths_and_laps_for_a_pool_swim() {
+        let fit = encode(&fixture::swim_move(&[
+            &[(0.0, 25.0, 12), (25.0, 27.0, 14)],
+            &[(90.0, 20.0, 10)],
+        ]));
+
+        let lengths = messages(&fit, MesgNum::Length);
+        let summary = lengths
+            .iter()
+            .map(|l| {
+                (
+                    field(l, "length_type").unwrap(),
+                    field(l, "total_elapsed_time").unwrap(),
+                    field(l, "total_strokes"),
+                )
+            })
+            .collect::<Vec<_>>();
+        let length = |kind: &str, sec: &str, strokes: Option<&str>| {
+            (kind.to_string(), sec.to_string(), strokes.map(String::from))
+        };
+        assert_eq!(
+            summary,
+            vec![
+                length("active", "25", Some("12")),
+                length("active", "27", Some("14")),
+                length("idle", "38", None),
+                length("active", "20", Some("10")),
+            ]
+        );
+
+        let laps = messages(&fit, MesgNum::Lap);
+        let lap_lengths = laps
+            .iter()
+            .map(|l| {
+                (
+                    field(l, "first_length_index").unwrap(),
+                    field(l, "num_lengths").unwrap(),
+                    field(l, "total_distance").unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lap_lengths,
+            vec![
+                ("0".to_string(), "2".to_string(), "50".to_string()),
+                ("2".to_string(), "1".to_string(), "0".to_string()),
+                ("3".to_string(), "1".to_string(), "25".to_string()),
+            ]
+        );
+
+        let sessions = messages(&fit, MesgNum::Session);
+        assert_eq!(
+            field(&sessions[0], "sub_sport").as_deref(),
+            Some("lap_swimming")
+        );
+        assert_eq!(field(&sessions[0], "num_laps").as_deref(), Some("3"));
+        assert_eq!(field(&sessions[0], "total_strokes").as_deref(), Some("36"));
+    }
+}
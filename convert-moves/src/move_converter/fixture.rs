@@ -0,0 +1,97 @@
+use {
+    super::{
+        multisport::SportChange,
+        sample_rec::{Position, TrkPt},
+        sport::Sport,
+        swim::{Interval, Length, Style, Swim},
+        Move,
+    },
+    chrono::{DateTime, Duration, FixedOffset, TimeZone, Utc},
+};
+
+// Moves built by hand for the tests, rather than parsed from .sml
+// files.  They all start at 2019-06-01T07:30:00Z, which was 09:30 in
+// Central Europe.
+
+pub(crate) fn start_utc() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2019, 6, 1, 7, 30, 0).unwrap()
+}
+
+pub(crate) fn point(sec: i64, position: Option<(f32, f32)>, distance_m: f32) -> TrkPt {
+    TrkPt {
+        position: position.map(|(latitude_degrees, longitude_degrees)| Position {
+            latitude_degrees,
+            longitude_degrees,
+        }),
+        new_segment: false,
+        time_utc: start_utc() + Duration::seconds(sec),
+        hr_bpm: Some(140),
+        cadence_ffm: Some(85),
+        temperature_c: 21.0,
+        distance_m,
+        altitude_m: 100.0,
+        sea_level_pressure_millibar: 1013,
+        speed_mps: 3.0,
+        vertical_speed_mps: 0.0,
+    }
+}
+
+pub(crate) fn track_move(sport: Sport, points: Vec<TrkPt>) -> Move {
+    Move {
+        local_time: (start_utc() + Duration::hours(2)).naive_utc(),
+        start_utc: Some(start_utc()),
+        end_utc: None,
+        utc_offset: FixedOffset::east_opt(2 * 60 * 60),
+        sport,
+        points,
+        swim: None,
+        sport_changes: Vec::new(),
+    }
+}
+
+// Intervals of (start, duration, strokes) lengths in a 25 m pool.
+pub(crate) fn swim_move(intervals: &[&[(f64, f64, u16)]]) -> Move {
+    let intervals = intervals
+        .iter()
+        .map(|lengths| Interval {
+            lengths: lengths
+                .iter()
+                .map(|&(start_sec, duration_sec, strokes)| Length {
+                    start_sec,
+                    duration_sec,
+                    strokes,
+                    style: Style::Freestyle,
+                })
+                .collect(),
+        })
+        .collect();
+    Move {
+        swim: Some(Swim {
+            pool_length_m: 25.0,
+            intervals,
+        }),
+        ..track_move(Sport::PoolSwimming, Vec::new())
+    }
+}
+
+// An open water swim, a transition, a ride and a transition after it,
+// with a point every 100 s that's 50 m further on.
+pub(crate) fn multisport_move() -> Move {
+    let points = (0..=6)
+        .map(|i| {
+            let step = i as f32;
+            point(i * 100, Some((46.85 + step * 0.001, -121.76)), step * 50.0)
+        })
+        .collect();
+    let sport_changes = [(0.0, 6), (200.0, 1), (300.0, 4), (500.0, 2)]
+        .iter()
+        .map(|&(elapsed_sec, activity_type)| SportChange {
+            elapsed_sec,
+            activity_type,
+        })
+        .collect();
+    Move {
+        sport_changes,
+        ..track_move(Sport::Triathlon, points)
+    }
+}
@@ -0,0 +1,234 @@
+use {
+    super::{
+        sample_rec::{Position, SampleRec, TrkPt},
+        Config,
+    },
+    anyhow::Result,
+    std::{
+        convert::TryInto,
+        fmt::{self, Display, Formatter},
+        str::FromStr,
+    },
+};
+
+// The watch keeps recording heart rate, distance and so on when it
+// loses the GPS signal (tunnels, tree cover, tall buildings), but the
+// position in the periodic samples is just whatever the last fix was,
+// or nothing at all if there hasn't been one yet.  A fix that's older
+// than --max-fix-age is treated as no fix at all.
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum GapPolicy {
+    // What we've always done: keep the stale position, and drop
+    // samples from before the first fix.
+    #[default]
+    Drop,
+    // Draw a straight line across the gap.
+    Interpolate,
+    // Start a new segment after the gap, and keep the samples in the
+    // gap without a position in formats that allow it.
+    Segment,
+}
+
+impl Display for GapPolicy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use GapPolicy::*;
+
+        match self {
+            Drop => "drop",
+            Interpolate => "interpolate",
+            Segment => "segment",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseGapPolicyError;
+
+impl Display for ParseGapPolicyError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "must be drop, interpolate or segment")
+    }
+}
+
+impl FromStr for GapPolicy {
+    type Err = ParseGapPolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use GapPolicy::*;
+
+        match s.to_lowercase().as_str() {
+            "drop" => Ok(Drop),
+            "interpolate" => Ok(Interpolate),
+            "segment" => Ok(Segment),
+            _ => Err(ParseGapPolicyError),
+        }
+    }
+}
+
+pub(crate) fn track_point(
+    sample: &SampleRec,
+    config: &Config,
+    last_fix_sec: Option<f64>,
+) -> Result<TrkPt> {
+    if config.gaps == GapPolicy::Drop {
+        return sample.try_into();
+    }
+    let fresh = last_fix_sec
+        .zip(sample.elapsed())
+        .is_some_and(|(fix, now)| now - fix <= config.max_fix_age_sec);
+    match sample.try_into() {
+        Ok(pt) if fresh => Ok(pt),
+        _ => TrkPt::without_position(sample),
+    }
+}
+
+pub(crate) fn fill(points: &mut [TrkPt], policy: GapPolicy) {
+    let mut last_fix = None;
+    let mut i = 0;
+    while i < points.len() {
+        if points[i].position.is_some() {
+            last_fix = Some(i);
+            i += 1;
+            continue;
+        }
+        while i < points.len() && points[i].position.is_none() {
+            i += 1;
+        }
+        // Nothing to do for a gap at the start or end of the move,
+        // since there's only a fix on one side of it.
+        if let (Some(before), true) = (last_fix, i < points.len()) {
+            match policy {
+                GapPolicy::Drop => {}
+                GapPolicy::Interpolate => interpolate(points, before, i),
+                GapPolicy::Segment => points[i].new_segment = true,
+            }
+        }
+    }
+}
+
+// Positions in the gap are spread along the straight line between the
+// fixes on either side according to the watch's distance, which comes
+// from the foot pod or the accelerometer and so keeps going in a
+// tunnel.  If the distance doesn't move, we go by time instead.
+fn interpolate(points: &mut [TrkPt], before: usize, after: usize) {
+    let (from, to) = match (points[before].position, points[after].position) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return,
+    };
+    let distance_span = points[after].distance_m - points[before].distance_m;
+    let time_span = (points[after].time_utc - points[before].time_utc).num_milliseconds() as f32;
+    for i in before + 1..after {
+        let fraction = if distance_span > 0.0 {
+            (points[i].distance_m - points[before].distance_m) / distance_span
+        } else if time_span > 0.0 {
+            (points[i].time_utc - points[before].time_utc).num_milliseconds() as f32 / time_span
+        } else {
+            0.0
+        }
+        .clamp(0.0, 1.0);
+        points[i].position = Some(Position {
+            latitude_degrees: from.latitude_degrees
+                + (to.latitude_degrees - from.latitude_degrees) * fraction,
+            longitude_degrees: from.longitude_degrees
+                + (to.longitude_degrees - from.longitude_degrees) * fraction,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::fixture, *};
+
+    // A gap at the start (before the first fix), one in the middle,
+    // during which the watch's distance keeps going, and one at the
+    // end.
+    fn track() -> Vec<TrkPt> {
+        [
+            (0, None, 0.0),
+            (10, Some((46.0, -121.0)), 0.0),
+            (20, None, 10.0),
+            (30, None, 30.0),
+            (40, Some((46.4, -121.4)), 40.0),
+            (50, None, 50.0),
+        ]
+        .iter()
+        .map(|&(sec, position, distance_m)| fixture::point(sec, position, distance_m))
+        .collect()
+    }
+
+    fn positions(points: &[TrkPt]) -> Vec<Option<(f32, f32)>> {
+        points
+            .iter()
+            .map(|pt| {
+                pt.position.map(|p| {
+                    // Rounded to get rid of f32 noise.
+                    let round = |degrees: f32| (degrees * 1000.0).round() / 1000.0;
+                    (round(p.latitude_degrees), round(p.longitude_degrees))
+                })
+            })
+            .collect()
+    }
+
+    fn new_segments(points: &[TrkPt]) -> Vec<bool> {
+        points.iter().map(|pt| pt.new_segment).collect()
+    }
+
+    #[test]
+    fn drop_leaves_the_gaps_alone() {
+        let mut points = track();
+        fill(&mut points, GapPolicy::Drop);
+        assert_eq!(positions(&points), positions(&track()));
+        assert_eq!(new_segments(&points), vec![false; 6]);
+    }
+
+    #[test]
+    fn interpolate_goes_by_distance() {
+        let mut points = track();
+        fill(&mut points, GapPolicy::Interpolate);
+        assert_eq!(
+            positions(&points),
+            vec![
+                None,
+                Some((46.0, -121.0)),
+                Some((46.1, -121.1)),
+                Some((46.3, -121.3)),
+                Some((46.4, -121.4)),
+                None,
+            ]
+        );
+        assert_eq!(new_segments(&points), vec![false; 6]);
+    }
+
+    #[test]
+    fn interpolate_goes_by_time_when_the_distance_doesnt_move() {
+        let mut points = track();
+        for pt in &mut points {
+            pt.distance_m = 0.0;
+        }
+        fill(&mut points, GapPolicy::Interpolate);
+        assert_eq!(
+            positions(&points)[2..4],
+            [Some((46.133, -121.133)), Some((46.267, -121.267))]
+        );
+    }
+
+    #[test]
+    fn segment_starts_a_new_segment_after_the_gap() {
+        let mut points = track();
+        fill(&mut points, GapPolicy::Segment);
+        assert_eq!(positions(&points), positions(&track()));
+        assert_eq!(
+            new_segments(&points),
+            vec![false, false, false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn policies_parse() {
+        assert_eq!("Segment".parse::<GapPolicy>().unwrap(), GapPolicy::Segment);
+        assert!("fill".parse::<GapPolicy>().is_err());
+        assert_eq!(GapPolicy::default().to_string(), "drop");
+    }
+}
@@ -0,0 +1,163 @@
+use {
+    super::{rfc3339, sample_rec::TrkPt, EventWriter, EventWriterExt, Move, WriteResult},
+    anyhow::Result,
+};
+
+pub(super) fn write(mv: &Move, mut writer: EventWriter) -> Result<()> {
+    writer.write_prelude(mv)?;
+    if mv.swim.is_some() {
+        eprintln!("pool swims have no track; use --format tcx or fit to keep the lengths");
+    }
+    // GPX has no way to have a track point without a position, so
+    // whatever happened while there was no fix is lost.
+    for pt in &mv.points {
+        if pt.position.is_none() {
+            continue;
+        }
+        if pt.new_segment {
+            writer.close_trkseg()?;
+            writer.open_trkseg()?;
+        }
+        pt.dump(&mut writer)?;
+    }
+    writer.write_postlude()
+}
+
+trait DumpToGpx {
+    fn dump(&self, writer: &mut EventWriter) -> Result<()>;
+}
+
+trait GpxWriterExt {
+    fn write_prelude(&mut self, mv: &Move) -> Result<()>;
+    fn write_postlude(self) -> Result<()>;
+    fn open_gpx(&mut self) -> WriteResult;
+    fn metadata(&mut self, mv: &Move) -> WriteResult;
+    fn open_trk(&mut self) -> WriteResult;
+    fn name(&mut self, name: &str) -> WriteResult;
+    fn trk_type(&mut self, trk_type: &str) -> WriteResult;
+    fn open_trkseg(&mut self) -> WriteResult;
+    fn close_trkseg(&mut self) -> WriteResult;
+    fn close_trk(&mut self) -> WriteResult;
+    fn close_gpx(&mut self) -> WriteResult;
+}
+
+impl GpxWriterExt for EventWriter {
+    fn write_prelude(&mut self, mv: &Move) -> Result<()> {
+        self.write_document_declaration()?;
+        self.open_gpx()?;
+        self.metadata(mv)?;
+        self.open_trk()?;
+        self.name("Move")?;
+        self.trk_type(&mv.sport.gpx_type())?;
+        self.open_trkseg()?;
+        Ok(())
+    }
+
+    fn write_postlude(mut self) -> Result<()> {
+        self.close_trkseg()?;
+        self.close_trk()?;
+        self.close_gpx()?;
+        Ok(())
+    }
+
+    fn open_gpx(&mut self) -> WriteResult {
+        let gpx = xml::writer::XmlEvent::start_element("gpx")
+            .attr("version", "1.1")
+            .attr("creator", "Movescount - http://www.movescount.com")
+            .ns("xsi", "http://www.w3.org/2001/XMLSchema-instance")
+            .attr("xsi:schemaLocation", "http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd http://www.cluetrust.com/XML/GPXDATA/1/0 http://www.cluetrust.com/Schemas/gpxdata10.xsd http://www.garmin.com/xmlschemas/TrackPointExtension/v1 http://www.garmin.com/xmlschemas/TrackPointExtensionv1.xsd")
+            .ns("gpxdata", "http://www.cluetrust.com/XML/GPXDATA/1/0")
+            .ns("gpxtpx", "http://www.garmin.com/xmlschemas/TrackPointExtension/v1")
+            .default_ns("http://www.topografix.com/GPX/1/1");
+        self.write(gpx)
+    }
+
+    // GPX has no place for the local time, so the UTC offset goes in
+    // the description.
+    fn metadata(&mut self, mv: &Move) -> WriteResult {
+        self.start_element("metadata")?;
+        if let Some(local_start) = mv.local_start() {
+            self.dump_element(
+                "desc",
+                format!("Local start time {}", local_start.to_rfc3339()),
+            )?;
+        }
+        self.dump_element("time", rfc3339(mv.start_utc()))?;
+        self.end_element()
+    }
+
+    fn open_trk(&mut self) -> WriteResult {
+        self.start_element("trk")
+    }
+
+    fn name(&mut self, name: &str) -> WriteResult {
+        self.start_element("name")?;
+        self.write(xml::writer::XmlEvent::characters(name))?;
+        self.end_element()
+    }
+
+    fn trk_type(&mut self, trk_type: &str) -> WriteResult {
+        self.dump_element("type", trk_type)
+    }
+
+    fn open_trkseg(&mut self) -> WriteResult {
+        self.start_element("trkseg")
+    }
+
+    fn close_trkseg(&mut self) -> WriteResult {
+        self.end_element()
+    }
+
+    fn close_trk(&mut self) -> WriteResult {
+        self.end_element()
+    }
+
+    fn close_gpx(&mut self) -> WriteResult {
+        self.end_element()
+    }
+}
+
+impl DumpToGpx for TrkPt {
+    fn dump(&self, writer: &mut EventWriter) -> Result<()> {
+        let position = match &self.position {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+        let lat = position.latitude();
+        let lon = position.longitude();
+        let trkpt = xml::writer::XmlEvent::start_element("trkpt")
+            .attr("lat", &lat)
+            .attr("lon", &lon);
+        writer.write(trkpt)?;
+        writer.dump_element("ele", self.altitude_m)?;
+        writer.dump_element("time", self.time())?;
+        writer.start_element("extensions")?;
+        writer.start_element("gpxtpx:TrackPointExtension")?;
+        if let Some(hr_bpm) = self.hr_bpm {
+            writer.dump_element("gpxtpx:hr", hr_bpm)?;
+        }
+        writer.end_element(/* gpxtpx */)?;
+
+        let has_cadence;
+        match self.cadence_ffm {
+            None => has_cadence = false,
+            Some(ffm) => {
+                has_cadence = true;
+                writer.dump_element("gpxdata:cadence", ffm)?;
+            }
+        }
+
+        writer.dump_element("gpxdata:temp", self.temperature_c)?;
+        writer.dump_element("gpxdata:distance", self.distance_m)?;
+
+        if has_cadence {
+            writer.dump_element("gpxdata:altitude", self.altitude_m)?;
+        }
+
+        writer.dump_element("gpxdata:seaLevelPressure", self.sea_level_pressure_millibar)?;
+        writer.dump_element("gpxdata:speed", self.speed_mps)?;
+        writer.dump_element("gpxdata:verticalSpeed", self.vertical_speed_mps)?;
+        writer.end_element(/* extensions */)?;
+        writer.end_element().map_err(|e| e.into())
+    }
+}
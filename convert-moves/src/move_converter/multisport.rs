@@ -0,0 +1,173 @@
+use super::{duration_from_sec, seconds_between, sport::Sport, swim::Swim, Move};
+
+// Multisport moves (e.g., triathlons) have a sample with an
+// <Events><Activity> element each time the sport changes.  Each leg
+// of the move runs from one of those samples to the next.
+
+type ActivityRecSetter = fn(&mut ActivityRec, String);
+
+pub(crate) fn setter_for(name: String) -> Option<ActivityRecSetter> {
+    SETTERS_FOR_ELEMENTS
+        .iter()
+        .find(|(elem, _)| elem == &name)
+        .map(|(_, setter)| *setter)
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ActivityRec {
+    activity_type: String,
+}
+
+impl ActivityRec {
+    fn activity_type(&mut self, value: String) {
+        self.activity_type = value;
+    }
+
+    pub(crate) fn has_event(&self) -> bool {
+        !self.activity_type.is_empty()
+    }
+
+    pub(crate) fn take_change(&mut self, elapsed_sec: Option<f64>) -> Option<SportChange> {
+        let rec = std::mem::take(self);
+        Some(SportChange {
+            elapsed_sec: elapsed_sec?,
+            activity_type: rec.activity_type.parse().ok()?,
+        })
+    }
+}
+
+static SETTERS_FOR_ELEMENTS: [(&str, ActivityRecSetter); 1] =
+    [("ActivityType", ActivityRec::activity_type)];
+
+#[derive(Debug)]
+pub(crate) struct SportChange {
+    pub(crate) elapsed_sec: f64,
+    pub(crate) activity_type: u32,
+}
+
+impl SportChange {
+    pub(crate) fn sport(&self, has_lengths: bool) -> Option<Sport> {
+        if Sport::is_transition(self.activity_type) {
+            None
+        } else {
+            Some(Sport::from_activity_type(self.activity_type, has_lengths))
+        }
+    }
+}
+
+impl Move {
+    pub(crate) fn is_multisport(&self) -> bool {
+        !self.sport_changes.is_empty()
+    }
+
+    // Splits a multisport move into one move per leg.  Everything
+    // before the first sport change belongs to the first leg.
+    pub(crate) fn legs(&self) -> Vec<Move> {
+        let start_utc = self.start_utc();
+        let mut bounds = self
+            .sport_changes
+            .iter()
+            .map(|c| c.elapsed_sec)
+            .collect::<Vec<_>>();
+        if let Some(first) = bounds.first_mut() {
+            *first = first.min(0.0);
+        }
+
+        self.sport_changes
+            .iter()
+            .enumerate()
+            .map(|(i, change)| {
+                let leg_start = bounds[i];
+                let leg_end = bounds.get(i + 1).copied();
+                let in_leg = |sec: f64| sec >= leg_start && leg_end.is_none_or(|end| sec < end);
+                let points = self
+                    .points
+                    .iter()
+                    .filter(|pt| in_leg(seconds_between(start_utc, pt.time_utc)))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let swim = self.swim.as_ref().and_then(|swim| {
+                    let intervals = swim
+                        .intervals
+                        .iter()
+                        .filter(|interval| in_leg(interval.start_sec()))
+                        .map(|interval| interval.shifted(-leg_start))
+                        .collect::<Vec<_>>();
+                    if intervals.is_empty() {
+                        None
+                    } else {
+                        Some(Swim {
+                            pool_length_m: swim.pool_length_m,
+                            intervals,
+                        })
+                    }
+                });
+                let sport = change.sport(swim.is_some()).unwrap_or(Sport::Transition);
+                Move {
+                    local_time: self.local_time + duration_from_sec(leg_start),
+                    start_utc: Some(self.utc_at(leg_start)),
+                    end_utc: leg_end.map(|end| self.utc_at(end)),
+                    utc_offset: self.utc_offset,
+                    sport,
+                    points,
+                    swim,
+                    sport_changes: Vec::new(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::fixture, *},
+        chrono::Duration,
+    };
+
+    fn summary(legs: &[Move]) -> Vec<(Sport, Vec<f64>, f64)> {
+        legs.iter()
+            .map(|leg| {
+                (
+                    leg.sport,
+                    leg.points
+                        .iter()
+                        .map(|pt| seconds_between(fixture::start_utc(), pt.time_utc))
+                        .collect(),
+                    leg.duration_sec(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn legs_run_from_one_sport_change_to_the_next() {
+        let legs = fixture::multisport_move().legs();
+        assert_eq!(
+            summary(&legs),
+            vec![
+                (Sport::OpenWaterSwimming, vec![0.0, 100.0], 200.0),
+                (Sport::Transition, vec![200.0], 100.0),
+                (Sport::Cycling, vec![300.0, 400.0], 200.0),
+                // The transition after the last sport lasts until the
+                // last point.
+                (Sport::Transition, vec![500.0, 600.0], 100.0),
+            ]
+        );
+        assert_eq!(legs[2].distance_m(), 50.0);
+        assert_eq!(
+            legs[2].local_time,
+            legs[0].local_time + Duration::seconds(300)
+        );
+    }
+
+    // Samples before the first sport change belong to the first leg.
+    #[test]
+    fn the_first_leg_starts_with_the_move() {
+        let mut mv = fixture::multisport_move();
+        mv.sport_changes[0].elapsed_sec = 20.0;
+        let legs = mv.legs();
+        assert_eq!(summary(&legs)[0].1, vec![0.0, 100.0]);
+        assert_eq!(legs[0].start_utc(), fixture::start_utc());
+    }
+}
@@ -20,6 +20,8 @@ pub(crate) fn setter_for(name: String) -> Option<SampleRecSetter> {
 #[derive(Debug, Default)]
 pub(crate) struct SampleRec {
     pub(crate) local_time: String, // Comes from Header, not Sample
+    pool_length_m: String,         // Ditto
+    activity_type: String,         // Ditto
     latitude_ster: String,
     longitude_ster: String,
     vertical_speed_mps: String,
@@ -33,6 +35,7 @@ pub(crate) struct SampleRec {
     elapsed_time_sec: String,
     sample_type: String,
     time_utc: String,
+    position_updated: bool,
 }
 
 impl SampleRec {
@@ -40,8 +43,17 @@ impl SampleRec {
         self.local_time = value;
     }
 
+    fn pool_length_m(&mut self, value: String) {
+        self.pool_length_m = value;
+    }
+
+    fn activity_type(&mut self, value: String) {
+        self.activity_type = value;
+    }
+
     fn latitude_ster(&mut self, value: String) {
         self.latitude_ster = value;
+        self.position_updated = true;
     }
 
     fn longitude_ster(&mut self, value: String) {
@@ -99,10 +111,35 @@ impl SampleRec {
     pub(crate) fn has_cadence(&self) -> bool {
         !self.cadence_ffs.is_empty()
     }
+
+    // Like everything else, the position carries over from one
+    // sample to the next, so we need to know whether this sample
+    // actually had one of its own.
+    pub(crate) fn take_position_update(&mut self) -> bool {
+        std::mem::take(&mut self.position_updated)
+    }
+
+    pub(crate) fn pool_length(&self) -> Option<f32> {
+        self.pool_length_m.parse().ok()
+    }
+
+    pub(crate) fn header_activity_type(&self) -> Option<u32> {
+        self.activity_type.parse().ok()
+    }
+
+    pub(crate) fn elapsed(&self) -> Option<f64> {
+        self.elapsed_time_sec.parse().ok()
+    }
+
+    pub(crate) fn utc(&self) -> Option<DateTime<Utc>> {
+        self.time_utc.parse().ok()
+    }
 }
 
-static SETTERS_FOR_ELEMENTS: [(&str, SampleRecSetter); 15] = [
+static SETTERS_FOR_ELEMENTS: [(&str, SampleRecSetter); 17] = [
     ("DateTime", SampleRec::local_time),
+    ("PoolLength", SampleRec::pool_length_m),
+    ("ActivityType", SampleRec::activity_type),
     ("GPSAltitude", SampleRec::altitude_m),
     ("Latitude", SampleRec::latitude_ster),
     ("Longitude", SampleRec::longitude_ster),
@@ -119,11 +156,11 @@ static SETTERS_FOR_ELEMENTS: [(&str, SampleRecSetter); 15] = [
     ("UTC", SampleRec::time_utc),
 ];
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct TrkPt {
-    latitude_degrees: f32,
-    longitude_degrees: f32,
-    time_utc: DateTime<Utc>,
+    pub(crate) position: Option<Position>,
+    pub(crate) new_segment: bool,
+    pub(crate) time_utc: DateTime<Utc>,
     pub(crate) hr_bpm: Option<u16>,
     pub(crate) cadence_ffm: Option<u16>,
     pub(crate) temperature_c: f32,
@@ -134,7 +171,13 @@ pub(crate) struct TrkPt {
     pub(crate) vertical_speed_mps: f32,
 }
 
-impl TrkPt {
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Position {
+    pub(crate) latitude_degrees: f32,
+    pub(crate) longitude_degrees: f32,
+}
+
+impl Position {
     pub(crate) fn latitude(&self) -> String {
         format!("{}", self.latitude_degrees)
     }
@@ -142,17 +185,21 @@ impl TrkPt {
     pub(crate) fn longitude(&self) -> String {
         format!("{}", self.longitude_degrees)
     }
+}
 
+impl TrkPt {
     pub(crate) fn time(&self) -> String {
         self.time_utc
             .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
     }
-}
 
-impl TryFrom<&SampleRec> for TrkPt {
-    type Error = anyhow::Error;
+    // For samples taken while the GPS had no fix, which still have
+    // heart rate, distance and so on.
+    pub(crate) fn without_position(value: &SampleRec) -> Result<Self> {
+        Self::from_sample(value, None)
+    }
 
-    fn try_from(value: &SampleRec) -> Result<Self, Self::Error> {
+    fn from_sample(value: &SampleRec, position: Option<Position>) -> Result<Self> {
         let cadence_ffm = if value.cadence_ffs.is_empty() {
             None
         } else {
@@ -164,10 +211,8 @@ impl TryFrom<&SampleRec> for TrkPt {
             Some(bpm_from_bps(value.hr_bps.parse().context("hr")?)?)
         };
         Ok(TrkPt {
-            latitude_degrees: degrees_from_ster(value.latitude_ster.parse().context("latitude")?),
-            longitude_degrees: degrees_from_ster(
-                value.longitude_ster.parse().context("longitude")?,
-            ),
+            position,
+            new_segment: false,
             time_utc: value.time_utc.parse()?,
             hr_bpm,
             cadence_ffm,
@@ -186,6 +231,20 @@ impl TryFrom<&SampleRec> for TrkPt {
     }
 }
 
+impl TryFrom<&SampleRec> for TrkPt {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &SampleRec) -> Result<Self, Self::Error> {
+        let position = Position {
+            latitude_degrees: degrees_from_ster(value.latitude_ster.parse().context("latitude")?),
+            longitude_degrees: degrees_from_ster(
+                value.longitude_ster.parse().context("longitude")?,
+            ),
+        };
+        Self::from_sample(value, Some(position))
+    }
+}
+
 fn degrees_from_ster(ster: f32) -> f32 {
     ster * 180.0 / PI
 }
@@ -0,0 +1,134 @@
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Sport {
+    Running,
+    Cycling,
+    MountainBiking,
+    IndoorCycling,
+    PoolSwimming,
+    OpenWaterSwimming,
+    Walking,
+    Trekking,
+    Sailing,
+    Kayaking,
+    Rowing,
+    Climbing,
+    AlpineSkiing,
+    Snowboarding,
+    CrossCountrySkiing,
+    Triathlon,
+    Multisport,
+    Transition,
+    Other,
+}
+
+impl Sport {
+    // The numbers are Movescount's activity ids, which is what the
+    // watch puts in <ActivityType>.  Swimming is the only one that
+    // needs more context, because the watch uses the same id for
+    // pool swims and open water swims.
+    pub(crate) fn from_activity_type(id: u32, has_lengths: bool) -> Self {
+        use Sport::*;
+
+        match id {
+            2 => Multisport,
+            3 => Running,
+            4 => Cycling,
+            5 => MountainBiking,
+            6 if has_lengths => PoolSwimming,
+            6 => OpenWaterSwimming,
+            10 => Trekking,
+            11 => Walking,
+            12 => Sailing,
+            13 => Kayaking,
+            14 => Rowing,
+            15 => Climbing,
+            16 => IndoorCycling,
+            18 => Triathlon,
+            19 => AlpineSkiing,
+            20 => Snowboarding,
+            21 => CrossCountrySkiing,
+            _ => Other,
+        }
+    }
+
+    // Within a multisport move, the watch switches to a
+    // non-specific activity between legs.
+    pub(crate) fn is_transition(id: u32) -> bool {
+        id == 1 || id == 2
+    }
+
+    // TCX only knows about three sports
+    pub(crate) fn tcx_sport(self) -> &'static str {
+        use Sport::*;
+
+        match self {
+            Running => "Running",
+            Cycling | MountainBiking | IndoorCycling => "Biking",
+            _ => "Other",
+        }
+    }
+
+    // FIT's sport and sub_sport enums
+    pub(crate) fn fit_sport(self) -> (u8, u8) {
+        use Sport::*;
+
+        match self {
+            Running => (1, 0),
+            Cycling => (2, 0),
+            MountainBiking => (2, 8),
+            IndoorCycling => (2, 6),
+            PoolSwimming => (5, 17),
+            OpenWaterSwimming => (5, 18),
+            Walking => (11, 0),
+            Trekking => (17, 0),
+            Sailing => (32, 0),
+            Kayaking => (41, 0),
+            Rowing => (15, 0),
+            Climbing => (31, 0),
+            AlpineSkiing => (13, 0),
+            Snowboarding => (14, 0),
+            CrossCountrySkiing => (12, 0),
+            Triathlon | Multisport => (18, 0),
+            Transition => (3, 0),
+            Other => (0, 0),
+        }
+    }
+
+    // GPX's <type> is free-form, but lower case is what everyone
+    // else seems to use.
+    pub(crate) fn gpx_type(self) -> String {
+        self.to_string().to_lowercase()
+    }
+}
+
+// This is what ends up in the filename, so no spaces.
+impl Display for Sport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use Sport::*;
+
+        let name = match self {
+            Running => "Running",
+            Cycling => "Cycling",
+            MountainBiking => "MountainBiking",
+            IndoorCycling => "IndoorCycling",
+            PoolSwimming => "Swimming",
+            OpenWaterSwimming => "OpenWaterSwimming",
+            Walking => "Walking",
+            Trekking => "Trekking",
+            Sailing => "Sailing",
+            Kayaking => "Kayaking",
+            Rowing => "Rowing",
+            Climbing => "Climbing",
+            AlpineSkiing => "AlpineSkiing",
+            Snowboarding => "Snowboarding",
+            CrossCountrySkiing => "CrossCountrySkiing",
+            Triathlon => "Triathlon",
+            Multisport => "Multisport",
+            Transition => "Transition",
+            Other => "Other",
+        };
+        name.fmt(f)
+    }
+}
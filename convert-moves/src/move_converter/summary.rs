@@ -0,0 +1,114 @@
+use super::{swim::Swim, Move};
+
+pub(super) fn print(filename: &str, mv: &Move) {
+    println!("{}", filename);
+    print_local_start(mv);
+    println!(
+        "  {}, {:.0} m in {}",
+        mv.sport,
+        mv.distance_m(),
+        hms(mv.duration_sec())
+    );
+    match &mv.swim {
+        Some(swim) => print_swim(swim),
+        None => println!("  {} track points", mv.points.len()),
+    }
+    print_track_distance(mv);
+}
+
+pub(super) fn print_multisport(filename: &str, mv: &Move, legs: &[Move]) {
+    println!("{}", filename);
+    print_local_start(mv);
+    println!(
+        "  {}, {:.0} m in {}",
+        mv.sport,
+        legs.iter().map(|leg| leg.distance_m()).sum::<f32>(),
+        hms(mv.duration_sec())
+    );
+    for leg in legs {
+        println!(
+            "  {} {}, {:.0} m in {}",
+            leg.local_time.format("%H:%M:%S"),
+            leg.sport,
+            leg.distance_m(),
+            hms(leg.duration_sec())
+        );
+    }
+}
+
+// How far the GPS track says we went, compared with the watch.
+fn print_track_distance(mv: &Move) {
+    if let Some(track_m) = mv.track_distance_m() {
+        let watch_m = f64::from(mv.distance_m());
+        if watch_m > 0.0 {
+            println!(
+                "  GPS track {:.0} m ({:+.1}% of the watch's distance)",
+                track_m,
+                (track_m / watch_m - 1.0) * 100.0
+            );
+        } else {
+            println!("  GPS track {:.0} m", track_m);
+        }
+    }
+}
+
+fn print_local_start(mv: &Move) {
+    match mv.local_start() {
+        Some(local_start) => println!(
+            "  started {} (UTC{})",
+            local_start.format("%Y-%m-%d %H:%M:%S"),
+            local_start.offset()
+        ),
+        None => println!(
+            "  started {} (unknown UTC offset)",
+            mv.local_time.format("%Y-%m-%d %H:%M:%S")
+        ),
+    }
+}
+
+fn print_swim(swim: &Swim) {
+    println!(
+        "  {} lengths of {} m, {} strokes",
+        swim.num_lengths(),
+        swim.pool_length_m,
+        swim.strokes()
+    );
+    for (i, interval) in swim.intervals.iter().enumerate() {
+        let n = interval.lengths.len();
+        println!(
+            "  interval {}: {} x {} m {} in {}, {}, SWOLF {}",
+            i + 1,
+            n,
+            swim.pool_length_m,
+            interval.style(),
+            hms(interval.duration_sec()),
+            pace(swim.pace_per_100m_sec(interval.duration_sec(), n)),
+            swolf(interval.swolf()),
+        );
+    }
+    println!(
+        "  swimming time {}, {}, SWOLF {}",
+        hms(swim.swimming_sec()),
+        pace(swim.pace_per_100m_sec(swim.swimming_sec(), swim.num_lengths())),
+        swolf(swim.swolf()),
+    );
+}
+
+fn hms(sec: f64) -> String {
+    let sec = sec.round() as u64;
+    format!("{}:{:02}:{:02}", sec / 3600, sec / 60 % 60, sec % 60)
+}
+
+fn pace(sec: Option<f64>) -> String {
+    match sec {
+        None => "no pace".to_string(),
+        Some(sec) => {
+            let sec = sec.round() as u64;
+            format!("{}:{:02}/100 m", sec / 60, sec % 60)
+        }
+    }
+}
+
+fn swolf(swolf: Option<f32>) -> String {
+    swolf.map_or_else(|| "n/a".to_string(), |s| format!("{:.1}", s))
+}
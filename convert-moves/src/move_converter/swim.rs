@@ -0,0 +1,435 @@
+use std::fmt::{self, Display, Formatter};
+
+// Pool swims have no GPS.  Instead, the watch records a sample with
+// an <Events><Swimming> element each time it detects a stroke or a
+// turn, as well as when the timer is started or stopped.  We collect
+// those events here and turn them into lengths, which are then
+// grouped into intervals that are separated by rests.
+
+type SwimRecSetter = fn(&mut SwimRec, String);
+
+pub(crate) fn setter_for(name: String) -> Option<SwimRecSetter> {
+    SETTERS_FOR_ELEMENTS
+        .iter()
+        .find(|(elem, _)| elem == &name)
+        .map(|(_, setter)| *setter)
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SwimRec {
+    event_type: String,
+    style: String,
+}
+
+impl SwimRec {
+    fn event_type(&mut self, value: String) {
+        self.event_type = value;
+    }
+
+    fn style(&mut self, value: String) {
+        self.style = value;
+    }
+
+    pub(crate) fn has_event(&self) -> bool {
+        !self.event_type.is_empty()
+    }
+
+    // Unlike SampleRec, whose values carry over from one sample to
+    // the next, each swimming event stands on its own.
+    pub(crate) fn take_event(&mut self, elapsed_sec: Option<f64>) -> Option<SwimEvent> {
+        let rec = std::mem::take(self);
+        let kind = match rec.event_type.as_str() {
+            "Start" => SwimEventKind::Start,
+            "Stroke" => SwimEventKind::Stroke,
+            "Turn" => SwimEventKind::Turn,
+            "Stop" => SwimEventKind::Stop,
+            _ => return None,
+        };
+        let style = if rec.style.is_empty() {
+            None
+        } else {
+            Some(Style::from(rec.style.as_str()))
+        };
+        Some(SwimEvent {
+            kind,
+            elapsed_sec: elapsed_sec?,
+            style,
+        })
+    }
+}
+
+static SETTERS_FOR_ELEMENTS: [(&str, SwimRecSetter); 2] = [
+    ("Type", SwimRec::event_type),
+    ("PoolLengthStyle", SwimRec::style),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SwimEventKind {
+    Start,
+    Stroke,
+    Turn,
+    Stop,
+}
+
+#[derive(Debug)]
+pub(crate) struct SwimEvent {
+    kind: SwimEventKind,
+    elapsed_sec: f64,
+    style: Option<Style>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Style {
+    Freestyle,
+    Backstroke,
+    Breaststroke,
+    Butterfly,
+    Drill,
+    Mixed,
+}
+
+impl Style {
+    // FIT's swim_stroke enum
+    pub(crate) fn fit_swim_stroke(self) -> u8 {
+        use Style::*;
+
+        match self {
+            Freestyle => 0,
+            Backstroke => 1,
+            Breaststroke => 2,
+            Butterfly => 3,
+            Drill => 4,
+            Mixed => 5,
+        }
+    }
+}
+
+impl Display for Style {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use Style::*;
+
+        let name = match self {
+            Freestyle => "freestyle",
+            Backstroke => "backstroke",
+            Breaststroke => "breaststroke",
+            Butterfly => "butterfly",
+            Drill => "drill",
+            Mixed => "mixed",
+        };
+        name.fmt(f)
+    }
+}
+
+// The watch isn't terribly consistent about how it names the styles,
+// so we're lenient and call anything we don't recognize "mixed".
+impl From<&str> for Style {
+    fn from(s: &str) -> Self {
+        use Style::*;
+
+        let s = s.to_lowercase();
+        if s.starts_with("free") || s == "crawl" {
+            Freestyle
+        } else if s.starts_with("back") {
+            Backstroke
+        } else if s.starts_with("breast") {
+            Breaststroke
+        } else if s.starts_with("butterfly") || s == "fly" {
+            Butterfly
+        } else if s.starts_with("drill") {
+            Drill
+        } else {
+            Mixed
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Length {
+    pub(crate) start_sec: f64,
+    pub(crate) duration_sec: f64,
+    pub(crate) strokes: u16,
+    pub(crate) style: Style,
+}
+
+impl Length {
+    pub(crate) fn swolf(&self) -> u32 {
+        self.duration_sec.round() as u32 + u32::from(self.strokes)
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Interval {
+    pub(crate) lengths: Vec<Length>,
+}
+
+impl Interval {
+    pub(crate) fn start_sec(&self) -> f64 {
+        self.lengths.first().map_or(0.0, |l| l.start_sec)
+    }
+
+    pub(crate) fn end_sec(&self) -> f64 {
+        self.lengths
+            .last()
+            .map_or(0.0, |l| l.start_sec + l.duration_sec)
+    }
+
+    pub(crate) fn duration_sec(&self) -> f64 {
+        self.end_sec() - self.start_sec()
+    }
+
+    pub(crate) fn strokes(&self) -> u32 {
+        self.lengths.iter().map(|l| u32::from(l.strokes)).sum()
+    }
+
+    // An interval's style is only a single style if all of its
+    // lengths agree.
+    pub(crate) fn style(&self) -> Style {
+        let mut styles = self.lengths.iter().map(|l| l.style);
+        match styles.next() {
+            None => Style::Mixed,
+            Some(first) => {
+                if styles.all(|s| s == first) {
+                    first
+                } else {
+                    Style::Mixed
+                }
+            }
+        }
+    }
+
+    pub(crate) fn swolf(&self) -> Option<f32> {
+        average_swolf(&self.lengths)
+    }
+
+    pub(crate) fn shifted(&self, sec: f64) -> Self {
+        Self {
+            lengths: self
+                .lengths
+                .iter()
+                .map(|l| Length {
+                    start_sec: l.start_sec + sec,
+                    ..*l
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Swim {
+    pub(crate) pool_length_m: f32,
+    pub(crate) intervals: Vec<Interval>,
+}
+
+impl Swim {
+    pub(crate) fn from_events(events: &[SwimEvent], pool_length_m: f32) -> Self {
+        use SwimEventKind::*;
+
+        let mut intervals = Vec::new();
+        let mut interval = Interval::default();
+        let mut length_start = None;
+        let mut strokes = 0;
+        let mut style = Style::Mixed;
+
+        for event in events {
+            let t = event.elapsed_sec;
+            if let Some(s) = event.style {
+                style = s;
+            }
+            match event.kind {
+                Start => {
+                    length_start = Some(t);
+                    strokes = 0;
+                }
+                Stroke => {
+                    if length_start.is_none() {
+                        length_start = Some(t);
+                    }
+                    strokes += 1;
+                }
+                Turn => {
+                    // A turn without a length to end, e.g., one after
+                    // the timer was stopped, only starts the next.
+                    if let Some(start) = length_start {
+                        interval.lengths.push(Length {
+                            start_sec: start,
+                            duration_sec: t - start,
+                            strokes,
+                            style,
+                        });
+                    }
+                    length_start = Some(t);
+                    strokes = 0;
+                }
+                Stop => {
+                    // The final length of an interval is ended by the
+                    // swimmer stopping the timer, not by a turn.
+                    if let (Some(start), true) = (length_start, strokes > 0) {
+                        interval.lengths.push(Length {
+                            start_sec: start,
+                            duration_sec: t - start,
+                            strokes,
+                            style,
+                        });
+                    }
+                    if !interval.lengths.is_empty() {
+                        intervals.push(std::mem::take(&mut interval));
+                    }
+                    length_start = None;
+                    strokes = 0;
+                }
+            }
+        }
+        if !interval.lengths.is_empty() {
+            intervals.push(interval);
+        }
+        Self {
+            pool_length_m,
+            intervals,
+        }
+    }
+
+    pub(crate) fn lengths(&self) -> impl Iterator<Item = &Length> {
+        self.intervals.iter().flat_map(|i| i.lengths.iter())
+    }
+
+    pub(crate) fn num_lengths(&self) -> usize {
+        self.intervals.iter().map(|i| i.lengths.len()).sum()
+    }
+
+    pub(crate) fn distance_m(&self) -> f32 {
+        self.num_lengths() as f32 * self.pool_length_m
+    }
+
+    pub(crate) fn swimming_sec(&self) -> f64 {
+        self.intervals.iter().map(|i| i.duration_sec()).sum()
+    }
+
+    pub(crate) fn strokes(&self) -> u32 {
+        self.intervals.iter().map(|i| i.strokes()).sum()
+    }
+
+    pub(crate) fn swolf(&self) -> Option<f32> {
+        average_swolf(self.lengths())
+    }
+
+    pub(crate) fn pace_per_100m_sec(&self, duration_sec: f64, lengths: usize) -> Option<f64> {
+        if lengths == 0 {
+            None
+        } else {
+            Some(duration_sec * 100.0 / (lengths as f64 * f64::from(self.pool_length_m)))
+        }
+    }
+}
+
+fn average_swolf<'a, I: IntoIterator<Item = &'a Length>>(lengths: I) -> Option<f32> {
+    let (n, total) = lengths
+        .into_iter()
+        .fold((0, 0), |(n, total), l| (n + 1, total + l.swolf()));
+    if n == 0 {
+        None
+    } else {
+        Some(total as f32 / n as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: SwimEventKind, elapsed_sec: f64) -> SwimEvent {
+        SwimEvent {
+            kind,
+            elapsed_sec,
+            style: None,
+        }
+    }
+
+    fn strokes(events: &mut Vec<SwimEvent>, from_sec: f64, n: u32) {
+        for i in 0..n {
+            events.push(event(SwimEventKind::Stroke, from_sec + f64::from(i) + 1.0));
+        }
+    }
+
+    fn lengths(swim: &Swim) -> Vec<Vec<(f64, f64, u16)>> {
+        swim.intervals
+            .iter()
+            .map(|interval| {
+                interval
+                    .lengths
+                    .iter()
+                    .map(|l| (l.start_sec, l.duration_sec, l.strokes))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lengths_are_grouped_into_intervals_between_pauses() {
+        use SwimEventKind::*;
+
+        // Two lengths of breaststroke, a rest, and a length of
+        // freestyle.
+        let mut events = vec![SwimEvent {
+            kind: Start,
+            elapsed_sec: 0.0,
+            style: Some(Style::Breaststroke),
+        }];
+        strokes(&mut events, 0.0, 12);
+        events.push(event(Turn, 25.0));
+        strokes(&mut events, 25.0, 14);
+        events.push(event(Stop, 52.0));
+        events.push(SwimEvent {
+            kind: Start,
+            elapsed_sec: 90.0,
+            style: Some(Style::Freestyle),
+        });
+        strokes(&mut events, 90.0, 10);
+        events.push(event(Stop, 110.0));
+
+        let swim = Swim::from_events(&events, 25.0);
+        assert_eq!(
+            lengths(&swim),
+            vec![
+                vec![(0.0, 25.0, 12), (25.0, 27.0, 14)],
+                vec![(90.0, 20.0, 10)]
+            ]
+        );
+        assert_eq!(swim.intervals[0].style(), Style::Breaststroke);
+        assert_eq!(swim.intervals[1].style(), Style::Freestyle);
+        assert_eq!(swim.num_lengths(), 3);
+        assert_eq!(swim.distance_m(), 75.0);
+        assert_eq!(swim.strokes(), 36);
+        assert_eq!(swim.swimming_sec(), 72.0);
+        assert_eq!(swim.intervals[0].lengths[0].swolf(), 37);
+    }
+
+    #[test]
+    fn a_turn_without_a_length_starts_one() {
+        use SwimEventKind::*;
+
+        let mut events = vec![event(Start, 0.0)];
+        strokes(&mut events, 0.0, 10);
+        events.push(event(Stop, 30.0));
+        // Pushing off again without starting the timer.
+        events.push(event(Turn, 60.0));
+        strokes(&mut events, 60.0, 11);
+        events.push(event(Turn, 85.0));
+
+        let swim = Swim::from_events(&events, 25.0);
+        assert_eq!(
+            lengths(&swim),
+            vec![vec![(0.0, 30.0, 10)], vec![(60.0, 25.0, 11)]]
+        );
+    }
+
+    #[test]
+    fn a_stop_without_strokes_doesnt_make_a_length() {
+        use SwimEventKind::*;
+
+        let events = vec![event(Start, 0.0), event(Stop, 5.0)];
+        let swim = Swim::from_events(&events, 25.0);
+        assert!(swim.intervals.is_empty());
+        assert_eq!(swim.swolf(), None);
+    }
+}
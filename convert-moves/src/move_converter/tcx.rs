@@ -0,0 +1,343 @@
+use {
+    super::{
+        rfc3339,
+        sample_rec::TrkPt,
+        sport::Sport,
+        swim::{Interval, Swim},
+        EventWriter, EventWriterExt, Move, WriteResult,
+    },
+    anyhow::Result,
+    chrono::{DateTime, Utc},
+    std::io::Write,
+};
+
+pub(super) fn write<W: Write>(mv: &Move, mut writer: EventWriter<W>) -> Result<()> {
+    writer.write_document_declaration()?;
+    writer.open_training_center_database()?;
+    writer.start_element("Activities")?;
+    write_activity(mv, None, &[], &mut writer)?;
+    writer.end_element(/* Activities */)?;
+    writer.end_element(/* TrainingCenterDatabase */)?;
+    Ok(())
+}
+
+// Each leg is an activity of its own, and a transition, if any, is a
+// lap that precedes the activity that follows it.  TCX only has room
+// for a transition between two sports, so one before the first sport
+// or after the last (e.g., when the watch is stopped in T2 rather
+// than at the finish) becomes an extra lap of that sport's activity.
+pub(super) fn write_multisport<W: Write>(
+    mv: &Move,
+    legs: &[Move],
+    mut writer: EventWriter<W>,
+) -> Result<()> {
+    writer.write_document_declaration()?;
+    writer.open_training_center_database()?;
+    writer.start_element("Activities")?;
+    writer.start_element("MultiSportSession")?;
+    writer.dump_element("Id", rfc3339(mv.start_utc()))?;
+    let last_sport = legs.iter().rposition(|leg| leg.sport != Sport::Transition);
+    let mut transition = None;
+    let mut first = true;
+    for (i, leg) in legs.iter().enumerate() {
+        if leg.sport == Sport::Transition {
+            transition = Some(leg);
+            continue;
+        }
+        let mut before = None;
+        if first {
+            writer.start_element("FirstSport")?;
+            first = false;
+            before = transition.take();
+        } else {
+            writer.start_element("NextSport")?;
+            if let Some(transition) = transition.take() {
+                write_track_lap(transition, &mut writer, "Transition")?;
+            }
+        }
+        let after = if Some(i) == last_sport {
+            &legs[i + 1..]
+        } else {
+            &[]
+        };
+        write_activity(leg, before, after, &mut writer)?;
+        writer.end_element(/* FirstSport or NextSport */)?;
+    }
+    writer.end_element(/* MultiSportSession */)?;
+    writer.end_element(/* Activities */)?;
+    writer.end_element(/* TrainingCenterDatabase */)?;
+    Ok(())
+}
+
+// before and after are transitions that are written as laps of their
+// own around the move's.
+fn write_activity<W: Write>(
+    mv: &Move,
+    before: Option<&Move>,
+    after: &[Move],
+    writer: &mut EventWriter<W>,
+) -> Result<()> {
+    writer.write(
+        xml::writer::XmlEvent::start_element("Activity").attr("Sport", mv.sport.tcx_sport()),
+    )?;
+    writer.dump_element("Id", rfc3339(mv.start_utc()))?;
+    if let Some(transition) = before {
+        write_track_lap(transition, writer, "Lap")?;
+    }
+    match &mv.swim {
+        Some(swim) => write_swim_laps(mv, swim, writer)?,
+        None => write_track_lap(mv, writer, "Lap")?,
+    }
+    for transition in after {
+        write_track_lap(transition, writer, "Lap")?;
+    }
+    // TCX has no place for the local time, so the UTC offset goes in
+    // the notes.
+    if let Some(local_start) = mv.local_start() {
+        writer.dump_element(
+            "Notes",
+            format!("Local start time {}", local_start.to_rfc3339()),
+        )?;
+    }
+    writer.end_element(/* Activity */)?;
+    Ok(())
+}
+
+fn write_track_lap<W: Write>(mv: &Move, writer: &mut EventWriter<W>, element: &str) -> Result<()> {
+    let hrs = mv.points.iter().filter_map(|pt| pt.hr_bpm);
+    let (n, total, max) = hrs.fold((0u32, 0u32, 0u16), |(n, total, max), hr| {
+        (n + 1, total + u32::from(hr), max.max(hr))
+    });
+    writer.open_lap(element, mv.start_utc())?;
+    writer.dump_element("TotalTimeSeconds", mv.duration_sec())?;
+    writer.dump_element("DistanceMeters", mv.distance_m())?;
+    writer.dump_element("Calories", 0)?;
+    if let Some(average) = (total + n / 2).checked_div(n) {
+        writer.dump_value("AverageHeartRateBpm", average)?;
+        writer.dump_value("MaximumHeartRateBpm", max)?;
+    }
+    writer.dump_element("Intensity", "Active")?;
+    writer.dump_element("TriggerMethod", "Manual")?;
+    // With --gaps segment, each stretch with a GPS fix gets its own
+    // <Track>, just like the <trkseg>s in a GPX file.
+    writer.start_element("Track")?;
+    for pt in &mv.points {
+        if pt.new_segment {
+            writer.end_element(/* Track */)?;
+            writer.start_element("Track")?;
+        }
+        pt.dump(mv, writer)?;
+    }
+    writer.end_element(/* Track */)?;
+    writer.end_element(/* Lap */)?;
+    Ok(())
+}
+
+// Each interval becomes an active lap, and the rests between them
+// become resting laps.  TCX has no notion of lengths, so we add a
+// trackpoint at the end of each length so that the splits survive,
+// and put the stroke counts and style in the lap's notes.
+fn write_swim_laps<W: Write>(mv: &Move, swim: &Swim, writer: &mut EventWriter<W>) -> Result<()> {
+    let mut distance_m = 0.0;
+    let mut previous: Option<&Interval> = None;
+    for interval in &swim.intervals {
+        if let Some(previous) = previous {
+            let rest_start = previous.end_sec();
+            writer.open_lap("Lap", mv.utc_at(rest_start))?;
+            writer.dump_element("TotalTimeSeconds", interval.start_sec() - rest_start)?;
+            writer.dump_element("DistanceMeters", 0)?;
+            writer.dump_element("Calories", 0)?;
+            writer.dump_element("Intensity", "Resting")?;
+            writer.dump_element("TriggerMethod", "Manual")?;
+            writer.end_element(/* Lap */)?;
+        }
+        let lap_distance_m = interval.lengths.len() as f32 * swim.pool_length_m;
+        writer.open_lap("Lap", mv.utc_at(interval.start_sec()))?;
+        writer.dump_element("TotalTimeSeconds", interval.duration_sec())?;
+        writer.dump_element("DistanceMeters", lap_distance_m)?;
+        writer.dump_element("Calories", 0)?;
+        writer.dump_element("Intensity", "Active")?;
+        writer.dump_element("TriggerMethod", "Manual")?;
+        writer.start_element("Track")?;
+        for length in &interval.lengths {
+            distance_m += swim.pool_length_m;
+            writer.start_element("Trackpoint")?;
+            writer.dump_element(
+                "Time",
+                rfc3339(mv.utc_at(length.start_sec + length.duration_sec)),
+            )?;
+            writer.dump_element("DistanceMeters", distance_m)?;
+            writer.end_element(/* Trackpoint */)?;
+        }
+        writer.end_element(/* Track */)?;
+        writer.dump_element(
+            "Notes",
+            format!(
+                "{} x {} m {}, {} strokes",
+                interval.lengths.len(),
+                swim.pool_length_m,
+                interval.style(),
+                interval.strokes()
+            ),
+        )?;
+        if interval.duration_sec() > 0.0 {
+            writer.start_element("Extensions")?;
+            writer.start_element("ns3:LX")?;
+            writer.dump_element(
+                "ns3:AvgSpeed",
+                f64::from(lap_distance_m) / interval.duration_sec(),
+            )?;
+            writer.end_element(/* LX */)?;
+            writer.end_element(/* Extensions */)?;
+        }
+        writer.end_element(/* Lap */)?;
+        previous = Some(interval);
+    }
+    Ok(())
+}
+
+trait DumpToTcx {
+    fn dump<W: Write>(&self, mv: &Move, writer: &mut EventWriter<W>) -> Result<()>;
+}
+
+trait TcxWriterExt {
+    fn open_training_center_database(&mut self) -> WriteResult;
+    fn open_lap(&mut self, element: &str, start: DateTime<Utc>) -> WriteResult;
+    fn dump_value<V: ToString>(&mut self, element: &str, value: V) -> WriteResult;
+}
+
+impl<W: Write> TcxWriterExt for EventWriter<W> {
+    fn open_training_center_database(&mut self) -> WriteResult {
+        let tcd = xml::writer::XmlEvent::start_element("TrainingCenterDatabase")
+            .ns("xsi", "http://www.w3.org/2001/XMLSchema-instance")
+            .attr("xsi:schemaLocation", "http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2 http://www.garmin.com/xmlschemas/TrainingCenterDatabasev2.xsd")
+            .ns("ns3", "http://www.garmin.com/xmlschemas/ActivityExtension/v2")
+            .default_ns("http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2");
+        self.write(tcd)
+    }
+
+    fn open_lap(&mut self, element: &str, start: DateTime<Utc>) -> WriteResult {
+        let start = rfc3339(start);
+        self.write(xml::writer::XmlEvent::start_element(element).attr("StartTime", &start))
+    }
+
+    // Heart rates are wrapped in a <Value>
+    fn dump_value<V: ToString>(&mut self, element: &str, value: V) -> WriteResult {
+        self.start_element(element)?;
+        self.dump_element("Value", value)?;
+        self.end_element()
+    }
+}
+
+impl DumpToTcx for TrkPt {
+    fn dump<W: Write>(&self, mv: &Move, writer: &mut EventWriter<W>) -> Result<()> {
+        writer.start_element("Trackpoint")?;
+        writer.dump_element("Time", self.time())?;
+        if let Some(position) = &self.position {
+            writer.start_element("Position")?;
+            writer.dump_element("LatitudeDegrees", position.latitude())?;
+            writer.dump_element("LongitudeDegrees", position.longitude())?;
+            writer.end_element(/* Position */)?;
+        }
+        writer.dump_element("AltitudeMeters", self.altitude_m)?;
+        writer.dump_element("DistanceMeters", self.distance_m)?;
+        if let Some(hr_bpm) = self.hr_bpm {
+            writer.dump_value("HeartRateBpm", hr_bpm)?;
+        }
+        let run_cadence = match (self.cadence_ffm, mv.sport.tcx_sport()) {
+            (Some(ffm), "Running") => Some(ffm),
+            (Some(ffm), _) => {
+                writer.dump_element("Cadence", ffm.min(254))?;
+                None
+            }
+            (None, _) => None,
+        };
+        writer.start_element("Extensions")?;
+        writer.start_element("ns3:TPX")?;
+        writer.dump_element("ns3:Speed", self.speed_mps)?;
+        if let Some(ffm) = run_cadence {
+            writer.dump_element("ns3:RunCadence", ffm)?;
+        }
+        writer.end_element(/* TPX */)?;
+        writer.end_element(/* Extensions */)?;
+        writer.end_element(/* Trackpoint */)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{super::fixture, *},
+        xml::reader::{EventReader, XmlEvent},
+    };
+
+    // The sports, transitions and laps of a multisport TCX, with each
+    // lap's start time.
+    fn outline(tcx: &[u8]) -> Vec<String> {
+        EventReader::new(tcx)
+            .into_iter()
+            .filter_map(|event| match event.unwrap() {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let attribute = |attribute| {
+                        attributes
+                            .iter()
+                            .find(|a| a.name.local_name == attribute)
+                            .map(|a| a.value.clone())
+                    };
+                    match name.local_name.as_str() {
+                        "FirstSport" | "NextSport" => Some(name.local_name),
+                        "Activity" => Some(format!("Activity {}", attribute("Sport")?)),
+                        "Lap" | "Transition" => {
+                            Some(format!("{} {}", name.local_name, attribute("StartTime")?))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn multisport_tcx(mv: &Move) -> Vec<u8> {
+        let mut tcx = Vec::new();
+        write_multisport(mv, &mv.legs(), super::super::xml_writer(&mut tcx)).unwrap();
+        tcx
+    }
+
+    #[test]
+    fn a_trailing_transition_is_kept() {
+        assert_eq!(
+            outline(&multisport_tcx(&fixture::multisport_move())),
+            vec![
+                "FirstSport",
+                "Activity Other",
+                "Lap 2019-06-01T07:30:00.000Z",
+                "NextSport",
+                "Transition 2019-06-01T07:33:20.000Z",
+                "Activity Biking",
+                "Lap 2019-06-01T07:35:00.000Z",
+                "Lap 2019-06-01T07:38:20.000Z",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_leading_transition_is_kept() {
+        let mut mv = fixture::multisport_move();
+        // Starting in T1, with the swim not recorded.
+        mv.sport_changes.remove(0);
+        assert_eq!(
+            outline(&multisport_tcx(&mv)),
+            vec![
+                "FirstSport",
+                "Activity Biking",
+                "Lap 2019-06-01T07:30:00.000Z",
+                "Lap 2019-06-01T07:35:00.000Z",
+                "Lap 2019-06-01T07:38:20.000Z",
+            ]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+
+// The header's DateTime is the local time at which the move started,
+// and every sample has a UTC time, but the offset between the two
+// isn't recorded anywhere.  The watch's clock and the GPS clock can
+// disagree by a few seconds, so we round to the nearest quarter hour,
+// which covers every real timezone, and give up if the difference
+// isn't close to one.
+
+const QUARTER_HOUR_SEC: i64 = 15 * 60;
+const MAX_CLOCK_SKEW_SEC: i64 = 2 * 60;
+const MAX_OFFSET_SEC: i64 = 14 * 60 * 60;
+
+pub(crate) fn offset_from_header(
+    local_time: NaiveDateTime,
+    start_utc: DateTime<Utc>,
+) -> Option<FixedOffset> {
+    let sec = (local_time - start_utc.naive_utc()).num_seconds();
+    let quarters = (sec as f64 / QUARTER_HOUR_SEC as f64).round() as i64;
+    let rounded = quarters * QUARTER_HOUR_SEC;
+    if (sec - rounded).abs() > MAX_CLOCK_SKEW_SEC || rounded.abs() > MAX_OFFSET_SEC {
+        return None;
+    }
+    FixedOffset::east_opt(rounded as i32)
+}
+
+#[cfg(feature = "tz-lookup")]
+pub(crate) fn offset_from_position(
+    latitude_degrees: f32,
+    longitude_degrees: f32,
+    utc: DateTime<Utc>,
+) -> Option<FixedOffset> {
+    use {
+        chrono::{Offset, TimeZone},
+        chrono_tz::Tz,
+    };
+
+    // Building the finder decompresses the boundaries, which takes a
+    // noticeable amount of time, so we only do it once.
+    thread_local! {
+        static FINDER: tzf_rs::DefaultFinder = tzf_rs::DefaultFinder::new();
+    }
+
+    let name = FINDER.with(|finder| {
+        finder
+            .get_tz_name(longitude_degrees.into(), latitude_degrees.into())
+            .to_string()
+    });
+    // tzf-rs and chrono-tz each bundle their own copy of the tz
+    // database, so a zone that's new in one may not be in the other.
+    let tz: Tz = match name.parse() {
+        Ok(tz) => tz,
+        Err(e) => {
+            eprintln!("unknown timezone {:?}: {}", name, e);
+            return None;
+        }
+    };
+    Some(tz.offset_from_utc_datetime(&utc.naive_utc()).fix())
+}
+
+#[cfg(not(feature = "tz-lookup"))]
+pub(crate) fn offset_from_position(
+    _latitude_degrees: f32,
+    _longitude_degrees: f32,
+    _utc: DateTime<Utc>,
+) -> Option<FixedOffset> {
+    // main won't take --tz-lookup without the feature.
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, chrono::TimeZone};
+
+    #[test]
+    fn offset_from_header_rounds_to_a_quarter_hour() {
+        let utc = Utc.with_ymd_and_hms(2019, 6, 1, 7, 30, 0).unwrap();
+        let local = |sec| utc.naive_utc() + chrono::Duration::seconds(sec);
+        // The watch's clock is a little ahead of GPS's.
+        assert_eq!(
+            offset_from_header(local(2 * 3600 + 50), utc),
+            FixedOffset::east_opt(2 * 3600)
+        );
+        assert_eq!(
+            offset_from_header(local(5 * 3600 + 45 * 60 - 30), utc),
+            FixedOffset::east_opt(5 * 3600 + 45 * 60)
+        );
+        assert_eq!(offset_from_header(local(2 * 3600 + 7 * 60), utc), None);
+        assert_eq!(offset_from_header(local(15 * 3600), utc), None);
+    }
+
+    #[cfg(feature = "tz-lookup")]
+    #[test]
+    fn offset_from_position_knows_about_summer_time() {
+        let summer = Utc.with_ymd_and_hms(2019, 6, 1, 7, 30, 0).unwrap();
+        let winter = Utc.with_ymd_and_hms(2019, 12, 1, 7, 30, 0).unwrap();
+        // Mount Rainier
+        assert_eq!(
+            offset_from_position(46.85, -121.76, summer),
+            FixedOffset::west_opt(7 * 3600)
+        );
+        assert_eq!(
+            offset_from_position(46.85, -121.76, winter),
+            FixedOffset::west_opt(8 * 3600)
+        );
+    }
+}
@@ -0,0 +1,2 @@
+// Synthetic: a stand-in for one of Movescount's scripts.
+console.log("app");
@@ -0,0 +1,292 @@
+use {
+    crate::{
+        browser::Browser,
+        move_page::{self, select},
+        shared::RateLimit,
+    },
+    anyhow::{anyhow, Result},
+    scraper::Html,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+        thread,
+    },
+    tracing::warn,
+    url::Url,
+};
+
+// The saved pages still load their stylesheets, scripts and images
+// from Movescount, which won't be there forever.  With --archive, they
+// (and the images and fonts that the stylesheets use) are saved in
+// assets/, next to the pages, and the pages are rewritten to use them.
+// Most pages use the same few stylesheets and scripts, so each is only
+// fetched once, and named after its URL so that it's found again.
+//
+// Something that can't be fetched, e.g., a tracker that's long gone,
+// is reported and left as it was, rather than failing the move.
+
+const ASSETS_DIR: &str = "assets";
+
+// The elements and attributes that refer to something the page needs.
+const REFERENCES: [(&str, &str); 5] = [
+    ("link[rel~=stylesheet][href]", "href"),
+    ("link[rel~=icon][href]", "href"),
+    ("script[src]", "src"),
+    ("img[src]", "src"),
+    ("input[type=image][src]", "src"),
+];
+
+// Stylesheets can also refer to images, fonts and other stylesheets,
+// but that's as deep as it goes.
+struct Archiver<'a, B> {
+    browser: &'a B,
+    rate_limit: &'a RateLimit,
+    page_dir: PathBuf,
+    assets_dir: PathBuf,
+}
+
+// Returns how many of the page's references are now to assets.
+pub(crate) fn archive_page<B: Browser>(
+    browser: &B,
+    page_url: &str,
+    html_path: &Path,
+    rate_limit: &RateLimit,
+) -> Result<usize> {
+    let page_dir = html_path.parent().unwrap_or_else(|| Path::new(""));
+    let archiver = Archiver {
+        browser,
+        rate_limit,
+        page_dir: page_dir.to_path_buf(),
+        assets_dir: page_dir.join(ASSETS_DIR),
+    };
+    fs::create_dir_all(&archiver.assets_dir)
+        .map_err(|e| anyhow!("{}: {}", archiver.assets_dir.display(), e))?;
+    let base = Url::parse(page_url)?;
+
+    let html = fs::read_to_string(html_path)?;
+    let mut rewrites = Vec::new();
+    for url in references(&html)? {
+        if let Some(name) = archiver.asset(&base, &url, true)? {
+            rewrites.push((url, format!("{}/{}", ASSETS_DIR, name)));
+        }
+    }
+    let mut archived = rewrites.len();
+    let html = move_page::rewrite_urls(&html, &rewrites);
+    // <style> elements and style attributes can have url()s too.
+    let html = rewrite_css(&html, |url| {
+        let name = archiver.asset(&base, url, false)?;
+        archived += name.is_some() as usize;
+        Ok(name.map(|name| format!("{}/{}", ASSETS_DIR, name)))
+    })?;
+    fs::write(html_path, html)?;
+    Ok(archived)
+}
+
+fn references(html: &str) -> Result<Vec<String>> {
+    let page = Html::parse_document(html);
+    let mut urls = Vec::new();
+    for (css, attribute) in REFERENCES {
+        for elem in select(page.root_element(), css)? {
+            if let Some(url) = elem.value().attr(attribute) {
+                if !urls.iter().any(|u| u == url) {
+                    urls.push(url.to_string());
+                }
+            }
+        }
+    }
+    Ok(urls)
+}
+
+impl<B: Browser> Archiver<'_, B> {
+    // The name of url's copy in assets/, fetching it if it isn't there
+    // yet.  None for what isn't fetched: what's already local, data:
+    // URLs and anything that fails.  A stylesheet's own url()s are
+    // archived too, unless it's one that another refers to.
+    fn asset(&self, base: &Url, url: &str, top_level: bool) -> Result<Option<String>> {
+        let url = url.trim();
+        if url.is_empty()
+            || url.starts_with('#')
+            || url.starts_with(&format!("{}/", ASSETS_DIR))
+            || self.is_local(url)
+        {
+            return Ok(None);
+        }
+        let url = match base.join(url) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => url,
+            _ => return Ok(None),
+        };
+        let name = asset_name(&url);
+        let path = self.assets_dir.join(&name);
+        if path.exists() {
+            return Ok(Some(name));
+        }
+        self.rate_limit.wait();
+        let mut data = match self.browser.fetch(url.as_str()) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("couldn't archive {:#}", e);
+                return Ok(None);
+            }
+        };
+        if top_level && name.ends_with(".css") {
+            // The stylesheet's copy is in assets/ too, so its url()s
+            // are to its neighbours.
+            let css = String::from_utf8_lossy(&data).into_owned();
+            data = rewrite_css(&css, |u| self.asset(&url, u, false))?.into_bytes();
+        }
+        // Another session may be archiving the same asset, and a
+        // half-written one would never be fetched again.
+        let part = self
+            .assets_dir
+            .join(format!(".{}.{:?}.part", name, thread::current().id()));
+        fs::write(&part, data).map_err(|e| anyhow!("{}: {}", part.display(), e))?;
+        fs::rename(&part, &path)?;
+        Ok(Some(name))
+    }
+
+    // E.g., the photos that --photos saved.
+    fn is_local(&self, url: &str) -> bool {
+        !url.contains(':') && !url.starts_with('/') && self.page_dir.join(url).exists()
+    }
+}
+
+// E.g., https://www.movescount.com/css/site.css?v=3 is saved as
+// 1a2b3c4d5e6f7a8b-site.css.  The hash tells apart files with the same
+// name (or different query strings), and has to be the same from one
+// run to the next, which std's Hasher doesn't promise.
+fn asset_name(url: &Url) -> String {
+    let hash = url
+        .as_str()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+        });
+    let file = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        .take(40)
+        .collect::<String>();
+    let file = file.trim_start_matches('.');
+    format!(
+        "{:016x}-{}",
+        hash,
+        if file.is_empty() { "index" } else { file }
+    )
+}
+
+// Replaces each url(...) in css with what f returns for it, if
+// anything.  Quoted or not, any quotes are kept.
+fn rewrite_css(css: &str, mut f: impl FnMut(&str) -> Result<Option<String>>) -> Result<String> {
+    let mut rewritten = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("url(") {
+        let (before, after) = rest.split_at(start + "url(".len());
+        rewritten.push_str(before);
+        let trimmed = after.trim_start();
+        rewritten.push_str(&after[..after.len() - trimmed.len()]);
+        let quote = trimmed.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let body = &trimmed[quote.map_or(0, char::len_utf8)..];
+        let end = match quote {
+            Some(quote) => body.find(quote),
+            None => body.find(')'),
+        };
+        let end = match end {
+            Some(end) => end,
+            None => {
+                rest = trimmed;
+                break;
+            }
+        };
+        let url = &body[..end];
+        let quote = quote.map(String::from).unwrap_or_default();
+        match f(url.trim())? {
+            Some(local) => rewritten.push_str(&format!("{}{}{}", quote, local, quote)),
+            None => rewritten.push_str(&format!("{}{}{}", quote, url, quote)),
+        }
+        rest = &body[end + quote.len()..];
+    }
+    rewritten.push_str(rest);
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::browser::fixture::{fixture_path, FixtureBrowser},
+        std::{process, time::Duration},
+    };
+
+    #[test]
+    fn rewrites_urls_in_css() {
+        let css =
+            r#"a { background: url( "../img/a.png" ) } b { src: url(b.woff2) } c { x: url('') }"#;
+        let rewritten = rewrite_css(css, |url| {
+            Ok(Some(url)
+                .filter(|url| !url.is_empty())
+                .map(|url| format!("[{}]", url)))
+        })
+        .unwrap();
+        assert_eq!(
+            rewritten,
+            r#"a { background: url( "[../img/a.png]" ) } b { src: url([b.woff2]) } c { x: url('') }"#
+        );
+        assert_eq!(
+            rewrite_css("url(unfinished", |_| Ok(None)).unwrap(),
+            "url(unfinished"
+        );
+    }
+
+    #[test]
+    fn archives_a_page_and_its_stylesheet() {
+        let dir = std::env::temp_dir().join(format!("retrieve-moves-archive-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let html_path = dir.join("Move_2019_06_01_101.html");
+        fs::copy(fixture_path("move_101.html"), &html_path).unwrap();
+        let browser = FixtureBrowser::new()
+            .resource("https://www.movescount.com/css/site.css?v=3", "site.css")
+            .resource("https://www.movescount.com/img/bg.png", "photo_creek.png")
+            .resource("https://cdn.movescount.com/js/app.js", "app.js");
+        let rate_limit = RateLimit::new(Duration::ZERO);
+
+        archive_page(
+            &browser,
+            "https://www.movescount.com/moves/move101",
+            &html_path,
+            &rate_limit,
+        )
+        .unwrap();
+        let name = |url: &str| asset_name(&Url::parse(url).unwrap());
+        let css = name("https://www.movescount.com/css/site.css?v=3");
+        let bg = name("https://www.movescount.com/img/bg.png");
+        let js = name("https://cdn.movescount.com/js/app.js");
+
+        let html = fs::read_to_string(&html_path).unwrap();
+        assert!(html.contains(&format!(r#"href="assets/{}""#, css)));
+        assert!(html.contains(&format!(r#"src="assets/{}""#, js)));
+        assert!(html.contains(&format!(r#"url('assets/{}')"#, bg)));
+        // There's no favicon to be had.
+        assert!(html.contains(r#"href="/favicon.ico""#));
+        let saved_css = fs::read_to_string(dir.join(ASSETS_DIR).join(&css)).unwrap();
+        assert!(saved_css.contains(&format!(r#"url("{}")"#, bg)));
+        assert_eq!(
+            fs::read(dir.join(ASSETS_DIR).join(&bg)).unwrap(),
+            fs::read(fixture_path("photo_creek.png")).unwrap()
+        );
+
+        // Everything's there now, so the second time, nothing's
+        // fetched.
+        fs::copy(fixture_path("move_101.html"), &html_path).unwrap();
+        let again = archive_page(
+            &FixtureBrowser::new(),
+            "https://www.movescount.com/moves/move101",
+            &html_path,
+            &rate_limit,
+        );
+        assert_eq!(again.unwrap(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use {
+    anyhow::{anyhow, bail, Result},
+    serde::Deserialize,
+    std::fmt::{self, Display, Formatter},
+    thirtyfour_sync::{prelude::*, By, ScriptArgs, WebDriver},
+};
+
+mod config;
+#[cfg(test)]
+pub(crate) mod fixture;
+
+pub(crate) use self::config::{BrowserConfig, BrowserKind};
+
+// Just enough of a web browser for MoveScraper, so that it can be
+// driven by something other than Firefox, e.g., the fixtures in the
+// tests.
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Locator<'a> {
+    Css(&'a str),
+    LinkText(&'a str),
+}
+
+impl Display for Locator<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use Locator::*;
+
+        match self {
+            Css(css) => write!(f, "css {:?}", css),
+            LinkText(text) => write!(f, "link text {:?}", text),
+        }
+    }
+}
+
+impl<'a> From<Locator<'a>> for By<'a> {
+    fn from(locator: Locator<'a>) -> Self {
+        match locator {
+            Locator::Css(css) => By::Css(css),
+            Locator::LinkText(text) => By::LinkText(text),
+        }
+    }
+}
+
+pub(crate) trait Browser {
+    type Element<'a>: Element
+    where
+        Self: 'a;
+
+    fn get(&mut self, url: &str) -> Result<()>;
+    fn current_url(&self) -> Result<String>;
+    fn find(&self, locator: Locator) -> Result<Self::Element<'_>>;
+    fn find_all(&self, locator: Locator) -> Result<Vec<Self::Element<'_>>>;
+    // Like find and find_all, but only looking inside parent.
+    fn find_in<'a>(
+        &'a self,
+        parent: &Self::Element<'a>,
+        locator: Locator,
+    ) -> Result<Self::Element<'a>>;
+    fn find_all_in<'a>(
+        &'a self,
+        parent: &Self::Element<'a>,
+        locator: Locator,
+    ) -> Result<Vec<Self::Element<'a>>>;
+    fn hover(&self, elem: &Self::Element<'_>) -> Result<()>;
+    // Looking for elements that aren't there is slow when the browser
+    // waits for them to show up.
+    fn without_implicit_wait<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T>;
+    // GETs url from the current page, with the session's cookies, for
+    // the things a page refers to, e.g., its photos.
+    fn fetch(&self, url: &str) -> Result<Vec<u8>>;
+    // For working out afterwards why a step failed.
+    fn screenshot(&self) -> Result<Vec<u8>>;
+    fn page_source(&self) -> Result<String>;
+}
+
+pub(crate) trait Element {
+    fn text(&self) -> Result<String>;
+    fn attribute(&self, name: &str) -> Result<Option<String>>;
+    fn outer_html(&self) -> Result<String>;
+    fn is_displayed(&self) -> Result<bool>;
+    fn click(&self) -> Result<()>;
+    fn send_keys(&self, keys: &str) -> Result<()>;
+}
+
+// WebDriver can only hand back JSON, so the body comes back as a data:
+// URL.
+const FETCH_SCRIPT: &str = r#"
+const [url, done] = arguments;
+fetch(url, { credentials: "include" })
+    .then(response => {
+        if (!response.ok) {
+            throw new Error(`${response.status} ${response.statusText}`);
+        }
+        return response.blob();
+    })
+    .then(blob => {
+        const reader = new FileReader();
+        reader.onload = () => done({ data: reader.result.split(",")[1] });
+        reader.onerror = () => done({ error: String(reader.error) });
+        reader.readAsDataURL(blob);
+    })
+    .catch(error => done({ error: String(error) }));
+"#;
+
+#[derive(Deserialize)]
+struct Fetched {
+    data: Option<String>,
+    error: Option<String>,
+}
+
+impl Browser for WebDriver {
+    type Element<'a> = WebElement<'a>;
+
+    fn get(&mut self, url: &str) -> Result<()> {
+        Ok(WebDriverCommands::get(self, url)?)
+    }
+
+    fn current_url(&self) -> Result<String> {
+        Ok(WebDriverCommands::current_url(self)?)
+    }
+
+    fn find(&self, locator: Locator) -> Result<WebElement<'_>> {
+        Ok(self.find_element(locator.into())?)
+    }
+
+    fn find_all(&self, locator: Locator) -> Result<Vec<WebElement<'_>>> {
+        Ok(self.find_elements(locator.into())?)
+    }
+
+    // The elements that WebElement finds only live as long as the
+    // WebElement, so they're rebuilt to live as long as the driver.
+    fn find_in<'a>(&'a self, parent: &WebElement<'a>, locator: Locator) -> Result<WebElement<'a>> {
+        let elem = parent.find_element(locator.into())?;
+        Ok(WebElement::new(self.session(), elem.element_id))
+    }
+
+    fn find_all_in<'a>(
+        &'a self,
+        parent: &WebElement<'a>,
+        locator: Locator,
+    ) -> Result<Vec<WebElement<'a>>> {
+        Ok(parent
+            .find_elements(locator.into())?
+            .into_iter()
+            .map(|elem| WebElement::new(self.session(), elem.element_id))
+            .collect())
+    }
+
+    fn hover(&self, elem: &WebElement<'_>) -> Result<()> {
+        Ok(self.action_chain().move_to_element_center(elem).perform()?)
+    }
+
+    fn without_implicit_wait<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let timeouts = self.get_timeouts()?;
+        self.set_implicit_wait_timeout(std::time::Duration::from_millis(0))?;
+        let result = f();
+        self.set_timeouts(timeouts)?;
+        result
+    }
+
+    fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        let mut args = ScriptArgs::new();
+        args.push(url)?;
+        let fetched: Fetched = self
+            .execute_async_script_with_args(FETCH_SCRIPT, &args)?
+            .convert()?;
+        match fetched {
+            Fetched {
+                data: Some(data), ..
+            } => base64::decode(data).map_err(|e| anyhow!("{}: {}", url, e)),
+            Fetched {
+                error: Some(error), ..
+            } => bail!("{}: {}", url, error),
+            _ => bail!("{}: nothing fetched", url),
+        }
+    }
+
+    fn screenshot(&self) -> Result<Vec<u8>> {
+        Ok(self.screenshot_as_png()?)
+    }
+
+    fn page_source(&self) -> Result<String> {
+        Ok(WebDriverCommands::page_source(self)?)
+    }
+}
+
+impl Element for WebElement<'_> {
+    fn text(&self) -> Result<String> {
+        Ok(WebElement::text(self)?)
+    }
+
+    fn attribute(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.get_attribute(name)?)
+    }
+
+    fn outer_html(&self) -> Result<String> {
+        Ok(WebElement::outer_html(self)?)
+    }
+
+    fn is_displayed(&self) -> Result<bool> {
+        Ok(WebElement::is_displayed(self)?)
+    }
+
+    fn click(&self) -> Result<()> {
+        Ok(WebElement::click(self)?)
+    }
+
+    fn send_keys(&self, keys: &str) -> Result<()> {
+        Ok(WebElement::send_keys(self, keys)?)
+    }
+}
@@ -0,0 +1,124 @@
+use {
+    anyhow::{anyhow, Result},
+    serde_json::json,
+    std::{
+        fmt::{self, Display, Formatter},
+        path::PathBuf,
+        str::FromStr,
+    },
+    thirtyfour_sync::{DesiredCapabilities, WebDriver},
+};
+
+// How to start a WebDriver session.  The defaults are what we've
+// always used: an interactive Firefox driven by a geckodriver on
+// localhost.  On a server, chromedriver and --headless do without a
+// display.
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) enum BrowserKind {
+    #[default]
+    Firefox,
+    Chrome,
+}
+
+impl Display for BrowserKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use BrowserKind::*;
+
+        match self {
+            Firefox => write!(f, "firefox"),
+            Chrome => write!(f, "chrome"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseBrowserKindError;
+
+impl Display for ParseBrowserKindError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "must be firefox or chrome")
+    }
+}
+
+impl FromStr for BrowserKind {
+    type Err = ParseBrowserKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use BrowserKind::*;
+
+        match s.to_ascii_lowercase().as_str() {
+            "firefox" => Ok(Firefox),
+            "chrome" | "chromium" => Ok(Chrome),
+            _ => Err(ParseBrowserKindError),
+        }
+    }
+}
+
+// What Movescount's exports are served as, so that Firefox saves them
+// without asking.
+const EXPORT_MIME_TYPES: &str = "application/gpx+xml,application/vnd.google-earth.kml+xml,\
+application/vnd.openxmlformats-officedocument.spreadsheetml.sheet,\
+application/vnd.garmin.tcx+xml,application/octet-stream,application/xml,text/xml";
+
+pub(crate) struct BrowserConfig {
+    pub(crate) webdriver_url: String,
+    pub(crate) browser: BrowserKind,
+    pub(crate) headless: bool,
+    pub(crate) download_dir: PathBuf,
+    // A browser profile (Firefox) or user data directory (Chrome) to
+    // use instead of a fresh one.
+    pub(crate) profile: Option<PathBuf>,
+}
+
+impl BrowserConfig {
+    pub(crate) fn connect(&self) -> Result<WebDriver> {
+        // The browser wants an absolute path, and may well have been
+        // started in some other directory.
+        let download_dir = self
+            .download_dir
+            .canonicalize()
+            .map_err(|e| anyhow!("{}: {}", self.download_dir.display(), e))?;
+        let download_dir = download_dir.to_string_lossy();
+
+        Ok(match self.browser {
+            BrowserKind::Firefox => {
+                let mut caps = DesiredCapabilities::firefox();
+                if self.headless {
+                    caps.set_headless()?;
+                }
+                if let Some(profile) = &self.profile {
+                    caps.add_firefox_arg("-profile")?;
+                    caps.add_firefox_arg(&profile.to_string_lossy())?;
+                }
+                caps.add_firefox_option(
+                    "prefs",
+                    json!({
+                        "browser.download.folderList": 2,
+                        "browser.download.dir": download_dir,
+                        "browser.download.useDownloadDir": true,
+                        "browser.helperApps.neverAsk.saveToDisk": EXPORT_MIME_TYPES,
+                    }),
+                )?;
+                WebDriver::new(&self.webdriver_url, &caps)?
+            }
+            BrowserKind::Chrome => {
+                let mut caps = DesiredCapabilities::chrome();
+                if self.headless {
+                    caps.set_headless()?;
+                }
+                if let Some(profile) = &self.profile {
+                    caps.add_chrome_arg(&format!("--user-data-dir={}", profile.display()))?;
+                }
+                caps.add_chrome_option(
+                    "prefs",
+                    json!({
+                        "download.default_directory": download_dir,
+                        "download.prompt_for_download": false,
+                    }),
+                )?;
+                WebDriver::new(&self.webdriver_url, &caps)?
+            }
+        })
+    }
+}
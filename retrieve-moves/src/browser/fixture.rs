@@ -0,0 +1,215 @@
+use {
+    super::{Browser, Element, Locator},
+    crate::move_page::text,
+    anyhow::{anyhow, bail, Result},
+    scraper::{ElementRef, Html, Selector},
+    std::{
+        cell::RefCell,
+        collections::HashMap,
+        fs,
+        path::{Path, PathBuf},
+        process,
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+// A Browser that serves the hand-written pages in the fixtures
+// directory instead of talking to Movescount.  All of the pages are
+// parsed up front, so that elements can borrow from them while the
+// "current" page changes underneath.  Clicking on a link with an href goes to
+// that page, and everything else (typing, hovering) does nothing.
+// Fetching a resource reads its fixture, and there's nothing to take a
+// screenshot of.
+
+pub(crate) struct FixtureBrowser {
+    pages: HashMap<String, Html>,
+    resources: HashMap<String, PathBuf>,
+    current: RefCell<Option<String>>,
+}
+
+impl FixtureBrowser {
+    pub(crate) fn new() -> Self {
+        Self {
+            pages: HashMap::new(),
+            resources: HashMap::new(),
+            current: RefCell::new(None),
+        }
+    }
+
+    pub(crate) fn page(mut self, url: &str, fixture: &str) -> Result<Self> {
+        let path = fixture_path(fixture);
+        let html = fs::read_to_string(&path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        self.pages
+            .insert(url.to_string(), Html::parse_document(&html));
+        Ok(self)
+    }
+
+    pub(crate) fn resource(mut self, url: &str, fixture: &str) -> Self {
+        self.resources
+            .insert(url.to_string(), fixture_path(fixture));
+        self
+    }
+
+    fn go(&self, url: &str) -> Result<()> {
+        if !self.pages.contains_key(url) {
+            bail!("no fixture for {}", url);
+        }
+        *self.current.borrow_mut() = Some(url.to_string());
+        Ok(())
+    }
+
+    fn document(&self) -> Result<&Html> {
+        let current = self.current.borrow();
+        let url = current.as_ref().ok_or_else(|| anyhow!("no page loaded"))?;
+        Ok(&self.pages[url])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct FixtureElement<'a> {
+    browser: &'a FixtureBrowser,
+    elem: ElementRef<'a>,
+}
+
+impl Browser for FixtureBrowser {
+    type Element<'a> = FixtureElement<'a>;
+
+    fn get(&mut self, url: &str) -> Result<()> {
+        self.go(url)
+    }
+
+    fn current_url(&self) -> Result<String> {
+        self.current
+            .borrow()
+            .clone()
+            .ok_or_else(|| anyhow!("no page loaded"))
+    }
+
+    fn find(&self, locator: Locator) -> Result<FixtureElement<'_>> {
+        self.find_all(locator)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no element matching {}", locator))
+    }
+
+    fn find_all(&self, locator: Locator) -> Result<Vec<FixtureElement<'_>>> {
+        self.find_all_in(
+            &FixtureElement {
+                browser: self,
+                elem: self.document()?.root_element(),
+            },
+            locator,
+        )
+    }
+
+    fn find_in<'a>(
+        &'a self,
+        parent: &FixtureElement<'a>,
+        locator: Locator,
+    ) -> Result<FixtureElement<'a>> {
+        self.find_all_in(parent, locator)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no element matching {}", locator))
+    }
+
+    fn find_all_in<'a>(
+        &'a self,
+        parent: &FixtureElement<'a>,
+        locator: Locator,
+    ) -> Result<Vec<FixtureElement<'a>>> {
+        Ok(select(parent.elem, locator)?
+            .into_iter()
+            .map(|elem| FixtureElement {
+                browser: self,
+                elem,
+            })
+            .collect())
+    }
+
+    fn hover(&self, _elem: &FixtureElement<'_>) -> Result<()> {
+        Ok(())
+    }
+
+    fn without_implicit_wait<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        f()
+    }
+
+    fn fetch(&self, url: &str) -> Result<Vec<u8>> {
+        match self.resources.get(url) {
+            Some(path) => fs::read(path).map_err(|e| anyhow!("{}: {}", path.display(), e)),
+            None => bail!("{}: 404 Not Found", url),
+        }
+    }
+
+    fn screenshot(&self) -> Result<Vec<u8>> {
+        bail!("fixtures can't be screenshotted")
+    }
+
+    fn page_source(&self) -> Result<String> {
+        Ok(self.document()?.root_element().html())
+    }
+}
+
+impl Element for FixtureElement<'_> {
+    fn text(&self) -> Result<String> {
+        Ok(text(self.elem))
+    }
+
+    fn attribute(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.elem.value().attr(name).map(|s| s.to_string()))
+    }
+
+    fn outer_html(&self) -> Result<String> {
+        Ok(self.elem.html())
+    }
+
+    fn is_displayed(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn click(&self) -> Result<()> {
+        match self.elem.value().attr("href") {
+            Some(href) => self.browser.go(href),
+            None => Ok(()),
+        }
+    }
+
+    fn send_keys(&self, _keys: &str) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn fixture_path(fixture: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(fixture)
+}
+
+// Each test's scraper gets its own failures directory, so that tests
+// running at the same time don't write over each other's captures.
+// Nothing is written there unless a step fails, and the tests that
+// make one fail remove it.
+pub(crate) fn failures_dir() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "retrieve-moves-failures-{}-{}",
+        process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+fn select<'a>(root: ElementRef<'a>, locator: Locator) -> Result<Vec<ElementRef<'a>>> {
+    Ok(match locator {
+        Locator::Css(css) => {
+            let selector = Selector::parse(css).map_err(|e| anyhow!("{}: {:?}", css, e))?;
+            root.select(&selector).collect()
+        }
+        Locator::LinkText(link_text) => {
+            let selector = Selector::parse("a").unwrap();
+            root.select(&selector)
+                .filter(|a| text(*a) == link_text)
+                .collect()
+        }
+    })
+}
@@ -0,0 +1,344 @@
+use {
+    anyhow::{anyhow, bail, Result},
+    serde::Deserialize,
+    std::{
+        env,
+        fmt::{self, Debug, Formatter},
+        fs,
+        io::{self, BufRead, ErrorKind, Write},
+        path::{Path, PathBuf},
+        process::{Command, Stdio},
+    },
+    zeroize::Zeroizing,
+};
+
+// Where the Movescount name and password come from, in order:
+//
+//   MOVESCOUNT_NAME and MOVESCOUNT_PASSWORD
+//   the credentials file, ~/.config/retrieve-moves/credentials.json
+//   --password-command (or "password_command" in the credentials file)
+//   a movescount.com entry in ~/.netrc, if its login is the name
+//   asking, without echoing the password
+//
+// Files with a password in them have to be private to their owner,
+// like ssh insists for its keys.  The password is zeroed when the
+// Credentials are dropped and is never printed, including in error
+// messages, which is why some errors below lose their details.
+
+const NAME_VAR: &str = "MOVESCOUNT_NAME";
+const PASSWORD_VAR: &str = "MOVESCOUNT_PASSWORD";
+const NETRC_MACHINES: [&str; 2] = ["movescount.com", "www.movescount.com"];
+
+pub(crate) struct Credentials {
+    pub(crate) name: String,
+    pub(crate) password: Zeroizing<String>,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("name", &self.name)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CredentialsFile {
+    name: Option<String>,
+    password: Option<Zeroizing<String>>,
+    password_command: Option<String>,
+}
+
+impl Credentials {
+    // file is --credentials, if given, in which case it has to exist.
+    pub(crate) fn resolve(file: Option<&Path>, password_command: Option<&str>) -> Result<Self> {
+        let config = match file {
+            Some(path) => read_credentials_file(path)?
+                .ok_or_else(|| anyhow!("{}: no such file", path.display()))?,
+            None => match default_credentials_file() {
+                Some(path) => read_credentials_file(&path)?.unwrap_or_default(),
+                None => CredentialsFile::default(),
+            },
+        };
+        let password_command = password_command.or(config.password_command.as_deref());
+        let mut netrc = Netrc::default();
+
+        let name = match env::var(NAME_VAR).ok().or(config.name) {
+            Some(name) => name,
+            None => match netrc.entry()?.and_then(|n| n.login.clone()) {
+                Some(name) => name,
+                None => prompt("Movescount name (email): ")?,
+            },
+        };
+        let password = match env::var(PASSWORD_VAR).ok().map(Zeroizing::new) {
+            Some(password) => password,
+            None => match (config.password, password_command) {
+                (Some(password), _) => password,
+                (None, Some(command)) => run_password_command(command)?,
+                (None, None) => match netrc.password_for(&name)? {
+                    Some(password) => password,
+                    None => Zeroizing::new(
+                        rpassword::prompt_password(format!("Movescount password for {}: ", name))
+                            .map_err(|e| anyhow!("couldn't read the password: {}", e))?,
+                    ),
+                },
+            },
+        };
+        if name.is_empty() || password.is_empty() {
+            bail!("empty Movescount name or password");
+        }
+        Ok(Self { name, password })
+    }
+}
+
+fn default_credentials_file() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("retrieve-moves").join("credentials.json"))
+}
+
+// None if there's no such file.
+fn read_credentials_file(path: &Path) -> Result<Option<CredentialsFile>> {
+    let data = match read_private(path)? {
+        None => return Ok(None),
+        Some(data) => data,
+    };
+    // serde_json's errors quote the offending input, which might be
+    // the password.
+    let config: CredentialsFile = serde_json::from_str(&data).map_err(|e| {
+        anyhow!(
+            "{}: not a valid credentials file (line {}, column {})",
+            path.display(),
+            e.line(),
+            e.column()
+        )
+    })?;
+    Ok(Some(config))
+}
+
+// The contents of a file that may have a password in it, as long as
+// it's not readable by anyone else.
+fn read_private(path: &Path) -> Result<Option<Zeroizing<String>>> {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => Zeroizing::new(data),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => bail!("{}: {}", path.display(), e),
+    };
+    check_permissions(path)?;
+    Ok(Some(data))
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        bail!(
+            "{} is accessible by others (mode {:o}), chmod 600 it",
+            path.display(),
+            mode & 0o777
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// E.g., "pass show movescount".  The first line of its output is the
+// password.
+fn run_password_command(command: &str) -> Result<Zeroizing<String>> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| anyhow!("couldn't run {:?}: {}", command, e))?;
+    let stdout = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        bail!("{:?} failed: {}", command, output.status);
+    }
+    let stdout = std::str::from_utf8(&stdout)
+        .map_err(|_| anyhow!("{:?} didn't print a UTF-8 password", command))?;
+    Ok(Zeroizing::new(
+        stdout.lines().next().unwrap_or_default().to_string(),
+    ))
+}
+
+fn prompt(message: &str) -> Result<String> {
+    eprint!("{}", message);
+    io::stderr().flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+#[derive(Default)]
+struct NetrcEntry {
+    login: Option<String>,
+    password: Option<Zeroizing<String>>,
+}
+
+// ~/.netrc is only read if the name or password is still missing
+// once everything before it has been tried, and then only once.
+#[derive(Default)]
+struct Netrc {
+    read: Option<Option<NetrcEntry>>,
+}
+
+impl Netrc {
+    fn entry(&mut self) -> Result<Option<&NetrcEntry>> {
+        if self.read.is_none() {
+            self.read = Some(read_netrc()?);
+        }
+        Ok(self.read.as_ref().and_then(|entry| entry.as_ref()))
+    }
+
+    // The entry's password is only for its own login, so it's not
+    // used with a name from somewhere else.
+    fn password_for(&mut self, name: &str) -> Result<Option<Zeroizing<String>>> {
+        Ok(self.entry()?.and_then(|entry| match &entry.login {
+            Some(login) if login != name => None,
+            _ => entry.password.clone(),
+        }))
+    }
+}
+
+// $NETRC or ~/.netrc.  Only the entry for Movescount matters; unlike
+// ftp, we don't fall back to "default", whose login and password are
+// meant for some other site.
+fn read_netrc() -> Result<Option<NetrcEntry>> {
+    let path = match env::var_os("NETRC")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".netrc")))
+    {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    match fs::read_to_string(&path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => bail!("{}: {}", path.display(), e),
+        Ok(data) => {
+            let data = Zeroizing::new(data);
+            let entry = parse_netrc(&data).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+            if entry.as_ref().is_some_and(|e| e.password.is_some()) {
+                check_permissions(&path)?;
+            }
+            Ok(entry)
+        }
+    }
+}
+
+// A file that ends where a value should be is an error, rather than
+// no entry at all.  The error names the keyword, never the value.
+fn parse_netrc(data: &str) -> Result<Option<NetrcEntry>> {
+    // Each entry's machine, None for "default".
+    let mut entries: Vec<(Option<&str>, NetrcEntry)> = Vec::new();
+    let mut tokens = data.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let mut value = || {
+            tokens
+                .next()
+                .ok_or_else(|| anyhow!("nothing after \"{}\"", token))
+        };
+        match token {
+            "machine" => {
+                let machine = value()?;
+                entries.push((Some(machine), NetrcEntry::default()));
+            }
+            "default" => entries.push((None, NetrcEntry::default())),
+            "login" => {
+                let login = value()?;
+                if let Some((_, entry)) = entries.last_mut() {
+                    entry.login = Some(login.to_string());
+                }
+            }
+            "password" => {
+                let password = Zeroizing::new(value()?.to_string());
+                if let Some((_, entry)) = entries.last_mut() {
+                    entry.password = Some(password);
+                }
+            }
+            "account" => {
+                value()?;
+            }
+            // A macro runs until a blank line, which split_whitespace
+            // can't see, and "default" has to be the last entry anyway.
+            "macdef" => break,
+            _ => {}
+        }
+    }
+    Ok(entries
+        .into_iter()
+        .find(|(machine, _)| machine.is_some_and(|m| NETRC_MACHINES.contains(&m)))
+        .map(|(_, entry)| entry))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_movescount_netrc_entry() {
+        let netrc = "machine example.com login someone password secret\n\
+                     machine www.movescount.com\n  login me@example.com\n  password hunter2\n\
+                     default login anonymous password guest\n";
+        let entry = parse_netrc(netrc).unwrap().unwrap();
+        assert_eq!(entry.login.as_deref(), Some("me@example.com"));
+        assert_eq!(
+            entry.password.as_deref().map(|p| p.as_str()),
+            Some("hunter2")
+        );
+    }
+
+    #[test]
+    fn ignores_the_default_netrc_entry() {
+        assert!(parse_netrc(
+            "machine example.com login someone\ndefault login anonymous password guest"
+        )
+        .unwrap()
+        .is_none());
+    }
+
+    #[test]
+    fn a_cut_off_netrc_entry_is_an_error() {
+        let e = parse_netrc("machine movescount.com login me@example.com password")
+            .err()
+            .unwrap();
+        assert_eq!(e.to_string(), "nothing after \"password\"");
+        assert!(parse_netrc("machine").is_err());
+    }
+
+    #[test]
+    fn a_netrc_password_is_only_for_its_login() {
+        let mut netrc = Netrc {
+            read: Some(
+                parse_netrc("machine movescount.com login me@example.com password hunter2")
+                    .unwrap(),
+            ),
+        };
+        assert!(netrc.password_for("me@example.com").unwrap().is_some());
+        assert!(netrc.password_for("someone@example.com").unwrap().is_none());
+        // Without a login, the password goes with whichever name.
+        let mut netrc = Netrc {
+            read: Some(parse_netrc("machine movescount.com password hunter2").unwrap()),
+        };
+        assert!(netrc.password_for("someone@example.com").unwrap().is_some());
+    }
+
+    #[test]
+    fn debug_hides_the_password() {
+        let credentials = Credentials {
+            name: "me@example.com".to_string(),
+            password: Zeroizing::new("hunter2".to_string()),
+        };
+        assert!(!format!("{:?}", credentials).contains("hunter2"));
+    }
+}
@@ -0,0 +1,241 @@
+use {
+    anyhow::{bail, Result},
+    chrono::{Datelike, Duration, NaiveDate},
+    std::{
+        convert::TryFrom,
+        fmt::{self, Display, Formatter},
+        ops::Bound::{self, Excluded, Included, Unbounded},
+        str::FromStr,
+    },
+};
+
+// Which moves to look for: --from and --to (inclusive), --before
+// (exclusive) or --last, e.g., 30d.  Once the ledger knows when the
+// account's first move was, there's no need to look any earlier, and
+// a range that ends before it is an error.  Until then, without a
+// start, moves_for_range finds the first move itself.
+
+// --last's argument: a number of days, weeks, months or years.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Period {
+    count: u32,
+    unit: PeriodUnit,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PeriodUnit {
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+impl Period {
+    // The first day of the period that ends with (and includes) date.
+    pub(crate) fn start(self, date: NaiveDate) -> Option<NaiveDate> {
+        use PeriodUnit::*;
+
+        let count = i64::from(self.count);
+        match self.unit {
+            Days => date.checked_sub_signed(Duration::days(count - 1)),
+            Weeks => date.checked_sub_signed(Duration::days(count * 7 - 1)),
+            Months => months_before(date, count)?.succ_opt(),
+            Years => months_before(date, count * 12)?.succ_opt(),
+        }
+    }
+}
+
+// The same day of the month, or the last day of a shorter month.
+fn months_before(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let month0 = i64::from(date.year()) * 12 + i64::from(date.month0()) - months;
+    let year = i32::try_from(month0.div_euclid(12)).ok()?;
+    let month = month0.rem_euclid(12) as u32 + 1;
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use PeriodUnit::*;
+
+        let unit = match self.unit {
+            Days => 'd',
+            Weeks => 'w',
+            Months => 'm',
+            Years => 'y',
+        };
+        write!(f, "{}{}", self.count, unit)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum ParsePeriodError {
+    NotNumeric,
+    Zero,
+    BadUnit,
+}
+
+impl Display for ParsePeriodError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use ParsePeriodError::*;
+
+        match self {
+            NotNumeric => write!(f, "must be a number followed by d, w, m or y, e.g., 30d"),
+            Zero => write!(f, "must be at least 1"),
+            BadUnit => write!(
+                f,
+                "must end with d, w, m or y (days, weeks, months or years)"
+            ),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = ParsePeriodError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use {ParsePeriodError::*, PeriodUnit::*};
+
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or(BadUnit)?;
+        let count = s[..split].parse().map_err(|_| NotNumeric)?;
+        if count == 0 {
+            return Err(Zero);
+        }
+        let unit = match &s[split..] {
+            "d" => Days,
+            "w" => Weeks,
+            "m" => Months,
+            "y" => Years,
+            _ => return Err(BadUnit),
+        };
+        Ok(Self { count, unit })
+    }
+}
+
+// The bounds to hand to moves_for_range.  The options that can't be
+// used together are kept apart by structopt.
+pub(crate) fn bounds(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    before: Option<NaiveDate>,
+    last: Option<Period>,
+    today: NaiveDate,
+    first_move: Option<NaiveDate>,
+) -> Result<(Bound<NaiveDate>, Bound<NaiveDate>)> {
+    if let Some(first) = first_move {
+        if let Some(to) = to.filter(|to| *to < first) {
+            bail!(
+                "--to {} is before the account's first move, on {}",
+                to,
+                first
+            );
+        }
+        if let Some(before) = before.filter(|before| *before <= first) {
+            bail!(
+                "--before {} isn't after the account's first move, on {}",
+                before,
+                first
+            );
+        }
+    }
+    let start = match (from, last) {
+        (Some(from), _) => Some(from),
+        (None, Some(last)) => match last.start(today) {
+            Some(start) => Some(start),
+            None => bail!("--last {} goes back too far", last),
+        },
+        (None, None) => None,
+    };
+    if let Some(start) = start {
+        if start > today {
+            bail!("{} hasn't happened yet", start);
+        }
+        if let Some(to) = to.filter(|to| *to < start) {
+            bail!("--to {} is before {}", to, start);
+        }
+        if let Some(before) = before.filter(|before| *before <= start) {
+            bail!("--before {} isn't after {}", before, start);
+        }
+    }
+    let start = match (start, first_move) {
+        (Some(start), Some(first)) => Some(start.max(first)),
+        (start, first) => start.or(first),
+    };
+    Ok((
+        start.map_or(Unbounded, Included),
+        match (to, before) {
+            (Some(to), _) => Included(to),
+            (None, Some(before)) => Excluded(before),
+            (None, None) => Unbounded,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn periods_include_today() {
+        let today = ymd(2020, 3, 31);
+        let start = |s: &str| s.parse::<Period>().unwrap().start(today).unwrap();
+        assert_eq!(start("1d"), today);
+        assert_eq!(start("30d"), ymd(2020, 3, 2));
+        assert_eq!(start("2w"), ymd(2020, 3, 18));
+        // There's no February 31st.
+        assert_eq!(start("1m"), ymd(2020, 3, 1));
+        assert_eq!(start("14m"), ymd(2019, 2, 1));
+        assert_eq!(start("1y"), ymd(2019, 4, 1));
+        assert!("0d".parse::<Period>().is_err());
+        assert!("30".parse::<Period>().is_err());
+        assert!("30x".parse::<Period>().is_err());
+    }
+
+    #[test]
+    fn bounds_are_checked() {
+        let today = ymd(2020, 6, 30);
+        let june = ymd(2020, 6, 1);
+        let may = ymd(2020, 5, 1);
+        assert_eq!(
+            bounds(Some(may), Some(june), None, None, today, None).unwrap(),
+            (Included(may), Included(june))
+        );
+        assert_eq!(
+            bounds(None, None, Some(june), None, today, None).unwrap(),
+            (Unbounded, Excluded(june))
+        );
+        assert!(bounds(Some(june), Some(may), None, None, today, None).is_err());
+        assert!(bounds(Some(june), None, Some(june), None, today, None).is_err());
+        assert!(bounds(Some(ymd(2020, 7, 1)), None, None, None, today, None).is_err());
+    }
+
+    #[test]
+    fn bounds_start_from_the_first_move() {
+        let today = ymd(2020, 6, 30);
+        let first = Some(ymd(2015, 3, 14));
+        assert_eq!(
+            bounds(None, None, None, None, today, first).unwrap(),
+            (Included(ymd(2015, 3, 14)), Unbounded)
+        );
+        assert_eq!(
+            bounds(Some(ymd(2010, 1, 1)), None, None, None, today, first).unwrap(),
+            (Included(ymd(2015, 3, 14)), Unbounded)
+        );
+        assert_eq!(
+            bounds(Some(ymd(2016, 1, 1)), None, None, None, today, first).unwrap(),
+            (Included(ymd(2016, 1, 1)), Unbounded)
+        );
+        let e = bounds(None, Some(ymd(2014, 12, 31)), None, None, today, first).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "--to 2014-12-31 is before the account's first move, on 2015-03-14"
+        );
+        assert!(bounds(None, None, Some(ymd(2015, 3, 14)), None, today, first).is_err());
+    }
+}
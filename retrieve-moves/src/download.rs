@@ -0,0 +1,90 @@
+use {
+    crate::export_format::ExportFormat,
+    anyhow::{bail, Result},
+    std::{
+        collections::{HashMap, HashSet},
+        fs,
+        path::{Path, PathBuf},
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+// Rather than sleeping for some number of seconds after clicking
+// "Export as GPX" and hoping for the best, we take a snapshot of the
+// download directory before the click and then wait for a new file
+// to show up, stop growing, lose Firefox's .part suffix and look
+// complete, e.g., end with the closing tag.
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// Firefox's and Chrome's
+const PARTIAL_SUFFIXES: [&str; 2] = [".part", ".crdownload"];
+
+pub(crate) struct DownloadWatcher {
+    dir: PathBuf,
+    timeout: Duration,
+    before: HashSet<PathBuf>,
+}
+
+impl DownloadWatcher {
+    pub(crate) fn new(dir: &Path, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            timeout,
+            before: entries(dir)?,
+        })
+    }
+
+    // Returns the path of the new file in the given format once it's
+    // complete.
+    pub(crate) fn wait_for(&self, format: ExportFormat) -> Result<PathBuf> {
+        let extension = format.extension();
+        let start = Instant::now();
+        let mut sizes = HashMap::new();
+        loop {
+            let new = entries(&self.dir)?
+                .into_iter()
+                .filter(|path| !self.before.contains(path))
+                .collect::<HashSet<_>>();
+            for path in &new {
+                if path.extension().is_none_or(|ext| ext != extension) {
+                    continue;
+                }
+                // Firefox creates an empty placeholder with the final
+                // name and writes to name.part, which it renames when
+                // it's done.  Chrome does much the same with
+                // .crdownload.
+                if PARTIAL_SUFFIXES
+                    .iter()
+                    .any(|suffix| new.contains(&partial(path, suffix)))
+                {
+                    continue;
+                }
+                let size = fs::metadata(path)?.len();
+                let previous = sizes.insert(path.clone(), size);
+                if size > 0 && previous == Some(size) && format.is_complete(path)? {
+                    return Ok(path.clone());
+                }
+            }
+            if start.elapsed() > self.timeout {
+                bail!(
+                    "no complete .{} file appeared in {} within {:?}",
+                    extension,
+                    self.dir.display(),
+                    self.timeout
+                );
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn entries(dir: &Path) -> Result<HashSet<PathBuf>> {
+    fs::read_dir(dir)?.map(|entry| Ok(entry?.path())).collect()
+}
+
+fn partial(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
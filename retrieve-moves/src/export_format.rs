@@ -0,0 +1,199 @@
+use {
+    anyhow::Result,
+    serde::{Deserialize, Serialize},
+    std::{
+        fmt::{self, Display, Formatter},
+        fs::File,
+        io::{Read, Seek, SeekFrom},
+        path::Path,
+        str::FromStr,
+    },
+};
+
+// The formats that a move's Tools menu can export.  GPX is all we
+// used to get, but it loses the laps and sport data that FIT and TCX
+// keep.
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ExportFormat {
+    Gpx,
+    Fit,
+    Kml,
+    Xlsx,
+    Tcx,
+}
+
+impl ExportFormat {
+    pub(crate) const ALL: [Self; 5] = [Self::Gpx, Self::Fit, Self::Kml, Self::Xlsx, Self::Tcx];
+
+    pub(crate) fn extension(self) -> &'static str {
+        use ExportFormat::*;
+
+        match self {
+            Gpx => "gpx",
+            Fit => "fit",
+            Kml => "kml",
+            Xlsx => "xlsx",
+            Tcx => "tcx",
+        }
+    }
+
+    pub(crate) fn link_text(self) -> &'static str {
+        use ExportFormat::*;
+
+        match self {
+            Gpx => "Export as GPX",
+            Fit => "Export as FIT",
+            Kml => "Export as KML",
+            Xlsx => "Export as XLSX",
+            Tcx => "Export as TCX",
+        }
+    }
+
+    // Whether a downloaded file is all there.  The XML formats end
+    // with their closing tag, a FIT file's header says how long it
+    // should be and an XLSX file (a zip) ends with its central
+    // directory.
+    pub(crate) fn is_complete(self, path: &Path) -> Result<bool> {
+        use ExportFormat::*;
+
+        match self {
+            Gpx => ends_with(path, b"</gpx>"),
+            Kml => ends_with(path, b"</kml>"),
+            Tcx => ends_with(path, b"</TrainingCenterDatabase>"),
+            Fit => fit_is_complete(path),
+            Xlsx => zip_is_complete(path),
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.extension().fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ParseExportFormatError(String);
+
+impl Display for ParseExportFormatError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown format {:?}, must be one of {}",
+            self.0,
+            ExportFormat::ALL
+                .iter()
+                .map(|format| format.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ParseExportFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| format.extension().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseExportFormatError(s.to_string()))
+    }
+}
+
+// Only the tail of the file matters, and exports can be big.
+fn ends_with(path: &Path, end_tag: &[u8]) -> Result<bool> {
+    let tail = tail(path, end_tag.len() as u64 + 64)?;
+    Ok(tail.trim_ascii_end().ends_with(end_tag))
+}
+
+fn tail(path: &Path, n: u64) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len - n.min(len)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    Ok(tail)
+}
+
+// The header's size (its first byte) and the data's size (bytes 4-7,
+// little endian) plus the two byte CRC at the end.
+fn fit_is_complete(path: &Path) -> Result<bool> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut header = [0; 12];
+    if len < header.len() as u64 {
+        return Ok(false);
+    }
+    file.read_exact(&mut header)?;
+    if &header[8..12] != b".FIT" {
+        return Ok(false);
+    }
+    let header_size = header[0] as u64;
+    let data_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+    Ok(len == header_size + data_size + 2)
+}
+
+// The end of central directory record is 22 bytes, plus a comment of
+// up to 64K that nobody uses.
+fn zip_is_complete(path: &Path) -> Result<bool> {
+    const END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x05\x06";
+
+    let tail = tail(path, 22 + 1024)?;
+    Ok(tail.len() >= 22
+        && tail
+            .windows(END_OF_CENTRAL_DIRECTORY.len())
+            .rposition(|w| w == END_OF_CENTRAL_DIRECTORY)
+            .is_some_and(|i| i + 22 <= tail.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::fs};
+
+    fn check(format: ExportFormat, name: &str, contents: &[u8]) -> bool {
+        let path = std::env::temp_dir().join(format!(
+            "retrieve-moves-{}-{}.{}",
+            std::process::id(),
+            name,
+            format
+        ));
+        fs::write(&path, contents).unwrap();
+        let complete = format.is_complete(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        complete
+    }
+
+    #[test]
+    fn recognizes_complete_files() {
+        use ExportFormat::*;
+
+        assert!(check(Gpx, "whole", b"<gpx><trk/></gpx>\n"));
+        assert!(!check(Gpx, "partial", b"<gpx><trk>"));
+        assert!(check(
+            Tcx,
+            "whole",
+            b"<TrainingCenterDatabase/>\n</TrainingCenterDatabase>"
+        ));
+
+        let mut fit = vec![14, 0x10, 0, 0, 4, 0, 0, 0];
+        fit.extend(b".FIT");
+        fit.extend([0, 0, 1, 2, 3, 4, 0, 0]);
+        assert!(check(Fit, "whole", &fit));
+        assert!(!check(Fit, "partial", &fit[..fit.len() - 3]));
+
+        let mut xlsx = b"PK\x03\x04 lots of zipped spreadsheet ".to_vec();
+        let end_of_central_directory = xlsx.len();
+        xlsx.extend(b"PK\x05\x06");
+        xlsx.extend([0; 18]);
+        assert!(check(Xlsx, "whole", &xlsx));
+        assert!(!check(
+            Xlsx,
+            "partial",
+            &xlsx[..end_of_central_directory + 10]
+        ));
+    }
+}
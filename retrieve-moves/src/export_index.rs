@@ -0,0 +1,222 @@
+use {
+    crate::{export_format::ExportFormat, ledger::Ledger},
+    anyhow::Result,
+    chrono::{NaiveDate, NaiveDateTime, Timelike},
+    std::{
+        collections::BTreeMap,
+        fs,
+        path::{Path, PathBuf},
+    },
+    tracing::warn,
+};
+
+// Globbing the download directory for every move gets slow once
+// there are a couple thousand exported files in it, so we read the
+// directory once and keep track of what we've downloaded since.
+//
+// Movescount names its exports Move_YYYY_MM_DD_HH_MM_SS_Sport.gpx
+// (or .fit, etc.), but the move's page only shows the hour and
+// minute, so that's what the files are keyed by.
+
+type Key = (ExportFormat, NaiveDate, u32, u32);
+
+const NAME_FORMAT: &str = "Move_%Y_%m_%d_%H_%M_%S";
+const NAME_FORMAT_LEN: usize = "Move_YYYY_MM_DD_HH_MM_SS".len();
+
+#[derive(Default)]
+pub(crate) struct ExportIndex {
+    files: BTreeMap<Key, Vec<PathBuf>>,
+}
+
+impl ExportIndex {
+    pub(crate) fn new(dir: &Path) -> Result<Self> {
+        let mut index = Self::default();
+        index.add_dir(dir)?;
+        Ok(index)
+    }
+
+    pub(crate) fn add_dir(&mut self, dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            self.add(&entry?.path());
+        }
+        Ok(())
+    }
+
+    // Returns false if the path isn't a Movescount export.
+    pub(crate) fn add(&mut self, path: &Path) -> bool {
+        match key(path) {
+            None => false,
+            Some(key) => {
+                let paths = self.files.entry(key).or_default();
+                if !paths.iter().any(|p| p == path) {
+                    paths.push(path.to_path_buf());
+                }
+                true
+            }
+        }
+    }
+
+    pub(crate) fn count_for_date(&self, format: ExportFormat, date: NaiveDate) -> usize {
+        self.files
+            .range((format, date, 0, 0)..=(format, date, 23, 59))
+            .map(|(_, paths)| paths.len())
+            .sum()
+    }
+
+    pub(crate) fn find(
+        &self,
+        format: ExportFormat,
+        date: NaiveDate,
+        hour: u32,
+        minute: u32,
+    ) -> &[PathBuf] {
+        self.files
+            .get(&(format, date, hour, minute))
+            .map_or(&[], |paths| paths.as_slice())
+    }
+
+    pub(crate) fn paths(&self, format: ExportFormat) -> impl Iterator<Item = &Path> {
+        self.files
+            .iter()
+            .filter(move |((f, _, _, _), _)| *f == format)
+            .flat_map(|(_, paths)| paths.iter().map(|p| p.as_path()))
+    }
+
+    pub(crate) fn rename(&mut self, from: &Path, to: &Path) {
+        for paths in self.files.values_mut() {
+            paths.retain(|p| p != from);
+        }
+        self.add(to);
+    }
+
+    // Files that aren't known to belong to a move, on a date with no
+    // known moves, or on a date with more files (of that format) than
+    // moves.  For the latter, we can't tell which of the files is the
+    // odd one out, so they're all listed.
+    pub(crate) fn orphans(&self, ledger: &Ledger) -> Vec<&Path> {
+        self.files
+            .iter()
+            .filter(|((format, date, _, _), _)| {
+                self.count_for_date(*format, *date) > ledger.count_on(*date)
+            })
+            .flat_map(|(_, paths)| paths.iter().map(|p| p.as_path()))
+            .filter(|path| !ledger.is_known_export(path))
+            .collect()
+    }
+
+    pub(crate) fn report_orphans(&self, ledger: &Ledger) {
+        let orphans = self.orphans(ledger);
+        if !orphans.is_empty() {
+            warn!(
+                "{} exported files don't match any known move:",
+                orphans.len()
+            );
+            for path in orphans {
+                warn!("  {}", path.display());
+            }
+        }
+    }
+}
+
+// Move_2019_06_01_07_30_12_Running.gpx becomes
+// Move_2019_06_01_07_30_12_Running_123456789.gpx
+pub(crate) fn with_move_id(path: &Path, id: u32) -> PathBuf {
+    let suffix = format!("_{}", id);
+    match (
+        path.file_stem().and_then(|stem| stem.to_str()),
+        path.extension().and_then(|ext| ext.to_str()),
+    ) {
+        (Some(stem), Some(ext)) if !stem.ends_with(&suffix) => {
+            path.with_file_name(format!("{}{}.{}", stem, suffix, ext))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+fn key(path: &Path) -> Option<Key> {
+    let format = path.extension()?.to_str()?.parse().ok()?;
+    let name = path.file_name()?.to_str()?;
+    let start = NaiveDateTime::parse_from_str(name.get(..NAME_FORMAT_LEN)?, NAME_FORMAT).ok()?;
+    Some((format, start.date(), start.hour(), start.minute()))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::move_scraper::DatedMoves};
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn exports_are_keyed_by_format_date_and_minute() {
+        assert_eq!(
+            key(Path::new("dl/Move_2019_06_01_07_30_12_Running.gpx")),
+            Some((ExportFormat::Gpx, ymd(2019, 6, 1), 7, 30))
+        );
+        // Once it's been renamed, too.
+        assert_eq!(
+            key(Path::new("Move_2019_06_01_07_30_12_Running_101.fit")),
+            Some((ExportFormat::Fit, ymd(2019, 6, 1), 7, 30))
+        );
+        assert_eq!(key(Path::new("Move_2019_06_01_07_30_12_Running.txt")), None);
+        assert_eq!(key(Path::new("Move_2019_06_01_Running.gpx")), None);
+        assert_eq!(key(Path::new("route.gpx")), None);
+    }
+
+    #[test]
+    fn the_move_id_is_added_once() {
+        let path = Path::new("dl/Move_2019_06_01_07_30_12_Running.gpx");
+        let renamed = with_move_id(path, 101);
+        assert_eq!(
+            renamed,
+            Path::new("dl/Move_2019_06_01_07_30_12_Running_101.gpx")
+        );
+        assert_eq!(with_move_id(&renamed, 101), renamed);
+        assert_eq!(
+            with_move_id(Path::new("dl/no_extension"), 101),
+            Path::new("dl/no_extension")
+        );
+    }
+
+    #[test]
+    fn orphans_are_unknown_files_on_dates_with_too_many() {
+        let mut ledger = Ledger::default();
+        ledger.merge(&[DatedMoves {
+            date: ymd(2019, 6, 1),
+            moves: vec!["101".parse().unwrap(), "102".parse().unwrap()],
+        }]);
+        let known = Path::new("Move_2019_06_01_07_30_12_Running_101.gpx");
+        ledger
+            .status_mut("101".parse().unwrap())
+            .unwrap()
+            .exports
+            .insert(ExportFormat::Gpx, known.to_path_buf());
+
+        let mut index = ExportIndex::default();
+        assert!(index.add(known));
+        assert!(index.add(Path::new("Move_2019_06_01_18_00_00_Cycling.gpx")));
+        // As many FIT files as moves, so they're fine.
+        assert!(index.add(Path::new("Move_2019_06_01_18_00_00_Cycling.fit")));
+        assert!(!index.add(Path::new("notes.txt")));
+        assert_eq!(index.count_for_date(ExportFormat::Gpx, ymd(2019, 6, 1)), 2);
+        assert!(index.orphans(&ledger).is_empty());
+
+        // A third GPX file on a date with two moves, and one on a date
+        // with none.
+        index.add(Path::new("Move_2019_06_01_19_00_00_Walking.gpx"));
+        index.add(Path::new("Move_2019_06_03_09_00_00_Running.gpx"));
+        assert_eq!(
+            index.orphans(&ledger),
+            vec![
+                Path::new("Move_2019_06_01_18_00_00_Cycling.gpx"),
+                Path::new("Move_2019_06_01_19_00_00_Walking.gpx"),
+                Path::new("Move_2019_06_03_09_00_00_Running.gpx"),
+            ]
+        );
+        assert_eq!(
+            index.find(ExportFormat::Gpx, ymd(2019, 6, 1), 7, 30),
+            [known.to_path_buf()]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use {
+    crate::{browser::Browser, move_page::write_json},
+    anyhow::{anyhow, Error, Result},
+    chrono::{Local, NaiveDateTime},
+    serde::Serialize,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+};
+
+// When a step fails because Movescount's pages aren't what we expect
+// (a link that isn't there, a start time that doesn't parse, a
+// calendar day without a number), the error alone doesn't say what
+// the page looked like instead.  So the browser's screenshot, the page
+// it was on and its DOM are saved in --failures-dir, e.g.,
+// move101-export-gpx.png, .html and .json, with the error in the
+// .json.  A later failure of the same step replaces them.
+//
+// Whatever can't be captured (e.g., a screenshot once the browser's
+// gone) is left out, rather than hiding the original error.
+
+#[derive(Serialize)]
+struct Failure {
+    step: String,
+    url: Option<String>,
+    error: String,
+    when: NaiveDateTime,
+}
+
+// Returns the .json file, which is written whatever else isn't.
+pub(crate) fn capture<B: Browser>(
+    browser: &B,
+    dir: &Path,
+    step: &str,
+    error: &Error,
+) -> Result<PathBuf> {
+    fs::create_dir_all(dir).map_err(|e| anyhow!("{}: {}", dir.display(), e))?;
+    let base = dir.join(file_stem(step));
+    if let Ok(png) = browser.screenshot() {
+        fs::write(base.with_extension("png"), png)?;
+    }
+    if let Ok(html) = browser.page_source() {
+        fs::write(base.with_extension("html"), html)?;
+    }
+    let json = base.with_extension("json");
+    write_json(
+        &json,
+        &Failure {
+            step: step.to_string(),
+            url: browser.current_url().ok(),
+            error: format!("{:#}", error),
+            when: Local::now().naive_local(),
+        },
+    )?;
+    Ok(json)
+}
+
+// Steps are named like move101 export gpx, which has to be made safe
+// to use as a file name.
+fn file_stem(step: &str) -> String {
+    step.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::browser::fixture::FixtureBrowser, std::process};
+
+    #[test]
+    fn captures_the_page_and_the_error() {
+        let dir = std::env::temp_dir().join(format!("retrieve-moves-failures-{}", process::id()));
+        let mut browser = FixtureBrowser::new()
+            .page("https://www.movescount.com/moves/move101", "move_101.html")
+            .unwrap();
+        browser
+            .get("https://www.movescount.com/moves/move101")
+            .unwrap();
+
+        let json = capture(
+            &browser,
+            &dir,
+            "move101 export gpx",
+            &anyhow!("no element matching link text \"Export as GPX\""),
+        )
+        .unwrap();
+        assert_eq!(json, dir.join("move101-export-gpx.json"));
+        let failure: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(failure["url"], "https://www.movescount.com/moves/move101");
+        assert_eq!(
+            failure["error"],
+            "no element matching link text \"Export as GPX\""
+        );
+        let html = fs::read_to_string(dir.join("move101-export-gpx.html")).unwrap();
+        assert!(html.contains("feed-content-top"));
+        // Fixtures can't be screenshotted.
+        assert!(!dir.join("move101-export-gpx.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
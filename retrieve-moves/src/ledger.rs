@@ -0,0 +1,440 @@
+use {
+    crate::{
+        export_format::ExportFormat,
+        move_scraper::{DatedMoves, Move},
+    },
+    anyhow::Result,
+    chrono::{NaiveDate, NaiveDateTime},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        io::{ErrorKind, Read, Write},
+        path::{Path, PathBuf},
+    },
+    tracing::info,
+};
+
+// Everything we know about each move: when it was, what we've saved
+// of it so far and what went wrong the last time we tried.  It's kept
+// in a JSON file that's rewritten (atomically, so a crash can't leave
+// half a file behind) after each move.
+//
+// This replaces saved_moves.json, which only had the dates and ids.
+// If there's no ledger yet, it's created from saved_moves.json.
+
+const LEDGER_FILENAME: &str = "moves_ledger.json";
+const SAVED_MOVES_FILENAME: &str = "saved_moves.json";
+
+#[derive(Default, Deserialize, Serialize)]
+pub(crate) struct Ledger {
+    // Where the ledger lives, which is the current directory except
+    // in the tests.
+    #[serde(skip)]
+    dir: PathBuf,
+    moves: BTreeMap<Move, MoveStatus>,
+    // The date of the account's first move, once a run without --from
+    // has gone back far enough to find it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_move: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct MoveStatus {
+    pub(crate) date: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) start: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) html: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) exports: BTreeMap<ExportFormat, PathBuf>,
+    // With --photos, the move's photos once they've been saved, which
+    // may be none at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) photos: Option<Vec<PathBuf>>,
+    // With --archive, set once the page's assets have been saved.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) archived: bool,
+    #[serde(default)]
+    pub(crate) attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) last_error: Option<String>,
+    // Set once we've run out of attempts, so later runs skip it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) failed: bool,
+}
+
+impl MoveStatus {
+    fn new(date: NaiveDate) -> Self {
+        Self {
+            date,
+            start: None,
+            html: None,
+            exports: BTreeMap::new(),
+            photos: None,
+            archived: false,
+            attempts: 0,
+            last_error: None,
+            failed: false,
+        }
+    }
+
+    pub(crate) fn export(&self, format: ExportFormat) -> Option<&Path> {
+        self.exports.get(&format).map(|path| path.as_path())
+    }
+
+    pub(crate) fn is_done(&self, wanted: &Wanted) -> bool {
+        self.html.is_some()
+            && wanted.formats.iter().all(|f| self.export(*f).is_some())
+            && (!wanted.photos || self.photos.is_some())
+            && (!wanted.archive || self.archived)
+    }
+}
+
+// What a run is to do for each move, besides saving its page.
+// formats is empty when we're not exporting.
+pub(crate) struct Wanted<'a> {
+    pub(crate) formats: &'a [ExportFormat],
+    pub(crate) photos: bool,
+    pub(crate) archive: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Attempt {
+    Skip,
+    OutOfAttempts,
+    Try,
+}
+
+// What saved_moves.json looked like.
+#[derive(Deserialize)]
+struct SavedDatedMoves {
+    date: NaiveDate,
+    moves: Vec<Move>,
+    #[serde(default)]
+    gpx_files: BTreeMap<Move, PathBuf>,
+}
+
+impl Ledger {
+    pub(crate) fn load() -> Result<Self> {
+        Self::load_in(Path::new(""))
+    }
+
+    fn load_in(dir: &Path) -> Result<Self> {
+        let mut ledger = match read(&dir.join(LEDGER_FILENAME))? {
+            Some(data) => serde_json::from_str(&data)?,
+            None => Self::migrate(dir)?,
+        };
+        ledger.dir = dir.to_path_buf();
+        Ok(ledger)
+    }
+
+    fn migrate(dir: &Path) -> Result<Self> {
+        let mut ledger = Self::default();
+        if let Some(data) = read(&dir.join(SAVED_MOVES_FILENAME))? {
+            let saved: Vec<SavedDatedMoves> = serde_json::from_str(&data)?;
+            for dm in saved {
+                for mv in dm.moves {
+                    let mut status = MoveStatus::new(dm.date);
+                    let html = dir.join(html_filename(mv, dm.date));
+                    if html.exists() {
+                        status.html = Some(html);
+                    }
+                    if let Some(gpx) = dm.gpx_files.get(&mv) {
+                        status.exports.insert(ExportFormat::Gpx, gpx.clone());
+                    }
+                    ledger.moves.insert(mv, status);
+                }
+            }
+            info!(
+                "created {} from {} ({} moves)",
+                LEDGER_FILENAME,
+                SAVED_MOVES_FILENAME,
+                ledger.moves.len()
+            );
+        }
+        Ok(ledger)
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        let tmp = self.dir.join(format!("{}.tmp", LEDGER_FILENAME));
+        let mut file = File::create(&tmp)?;
+        serde_json::to_writer_pretty(&mut file, self)?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join(LEDGER_FILENAME))?;
+        Ok(())
+    }
+
+    pub(crate) fn merge(&mut self, dated_moves: &[DatedMoves]) {
+        for dm in dated_moves {
+            for mv in &dm.moves {
+                self.moves
+                    .entry(*mv)
+                    .or_insert_with(|| MoveStatus::new(dm.date));
+            }
+        }
+    }
+
+    // Sorted by date, and by id within a date, which is the order in
+    // which they were uploaded.
+    pub(crate) fn moves(&self) -> Vec<Move> {
+        let mut moves = self
+            .moves
+            .iter()
+            .map(|(mv, status)| (status.date, *mv))
+            .collect::<Vec<_>>();
+        moves.sort();
+        moves.into_iter().map(|(_, mv)| mv).collect()
+    }
+
+    pub(crate) fn status(&self, mv: Move) -> Option<&MoveStatus> {
+        self.moves.get(&mv)
+    }
+
+    pub(crate) fn status_mut(&mut self, mv: Move) -> Option<&mut MoveStatus> {
+        self.moves.get_mut(&mv)
+    }
+
+    // Whether to try mv (again).  A move that's used up its attempts
+    // is marked as failed here.  Trying counts as an attempt straight
+    // away, so that one that kills us still counts, and attempted()
+    // starts the count again once one succeeds: it's only failures in
+    // a row that use attempts up, not runs that each save something
+    // new.
+    pub(crate) fn attempt(&mut self, mv: Move, wanted: &Wanted, max_attempts: u32) -> Attempt {
+        match self.moves.get_mut(&mv) {
+            None => Attempt::Skip,
+            Some(s) if s.failed || s.is_done(wanted) => Attempt::Skip,
+            Some(s) if s.attempts >= max_attempts => {
+                s.failed = true;
+                Attempt::OutOfAttempts
+            }
+            Some(s) => {
+                s.attempts += 1;
+                Attempt::Try
+            }
+        }
+    }
+
+    pub(crate) fn attempted<T>(&mut self, mv: Move, result: &Result<T>) {
+        if let Some(status) = self.moves.get_mut(&mv) {
+            match result {
+                Ok(_) => {
+                    status.attempts = 0;
+                    status.last_error = None;
+                }
+                Err(e) => status.last_error = Some(format!("{:#}", e)),
+            }
+        }
+    }
+
+    pub(crate) fn count_on(&self, date: NaiveDate) -> usize {
+        self.moves.values().filter(|s| s.date == date).count()
+    }
+
+    // For --retry-failed
+    pub(crate) fn clear_failures(&mut self) {
+        for status in self.moves.values_mut() {
+            if status.failed {
+                status.failed = false;
+                status.attempts = 0;
+            }
+        }
+    }
+
+    pub(crate) fn is_known_export(&self, path: &Path) -> bool {
+        self.moves
+            .values()
+            .any(|s| s.exports.values().any(|known| known == path))
+    }
+
+    pub(crate) fn first_move(&self) -> Option<NaiveDate> {
+        self.first_move
+    }
+
+    pub(crate) fn set_first_move(&mut self, date: NaiveDate) {
+        self.first_move = Some(date);
+    }
+
+    // The dates of the first and last moves.
+    pub(crate) fn date_span(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let dates = || self.moves.values().map(|s| s.date);
+        Some((dates().min()?, dates().max()?))
+    }
+
+    pub(crate) fn print_status(&self) {
+        let statuses = self.moves.values().collect::<Vec<_>>();
+        let (first, last) = match self.date_span() {
+            Some(span) => span,
+            None => {
+                println!("no moves yet");
+                return;
+            }
+        };
+        let n = statuses.len();
+        let html = statuses.iter().filter(|s| s.html.is_some()).count();
+        let failed = statuses.iter().filter(|s| s.failed).count();
+        println!("{} moves from {} to {}", n, first, last);
+        println!("  html saved:    {:5}, {} left", html, n - html);
+        // GPX is the default, so it's always shown, but the others
+        // only once something's been exported in that format.
+        for format in ExportFormat::ALL {
+            let exported = statuses
+                .iter()
+                .filter(|s| s.export(format).is_some())
+                .count();
+            if format == ExportFormat::Gpx || exported > 0 {
+                println!(
+                    "  {:4} exported: {:5}, {} left",
+                    format,
+                    exported,
+                    n - exported
+                );
+            }
+        }
+        let with_photos = statuses.iter().filter(|s| s.photos.is_some()).count();
+        if with_photos > 0 {
+            let photos = statuses
+                .iter()
+                .filter_map(|s| s.photos.as_ref())
+                .map(|photos| photos.len())
+                .sum::<usize>();
+            println!(
+                "  photos saved:  {:5}, {} left ({} photos)",
+                with_photos,
+                n - with_photos,
+                photos
+            );
+        }
+        let archived = statuses.iter().filter(|s| s.archived).count();
+        if archived > 0 {
+            println!("  archived:      {:5}, {} left", archived, n - archived);
+        }
+        println!("  failed:        {:5}", failed);
+
+        let failed = self
+            .moves
+            .iter()
+            .filter(|(_, s)| s.last_error.is_some())
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            println!("  {} moves with errors:", failed.len());
+            for (mv, s) in failed {
+                println!(
+                    "    {} ({}, {} attempts{}): {}",
+                    mv,
+                    s.date,
+                    s.attempts,
+                    if s.failed { ", failed" } else { "" },
+                    s.last_error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+    }
+}
+
+pub(crate) fn html_filename(mv: Move, date: NaiveDate) -> String {
+    format!("{}{}.html", date.format("Move_%Y_%m_%d_"), mv)
+}
+
+fn read(filename: &Path) -> Result<Option<String>> {
+    match File::open(filename) {
+        Ok(mut file) => {
+            let mut data = String::new();
+            file.read_to_string(&mut data)?;
+            Ok(Some(data))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(other) => Err(other.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::process};
+
+    #[test]
+    fn saved_moves_are_migrated() {
+        let dir = std::env::temp_dir().join(format!("retrieve-moves-ledger-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(SAVED_MOVES_FILENAME),
+            r#"[
+  {
+    "date": "2019-06-01",
+    "moves": [101, 102],
+    "gpx_files": { "101": "Move_2019_06_01_07_30_12_Running.gpx" }
+  },
+  { "date": "2019-06-03", "moves": [103] }
+]"#,
+        )
+        .unwrap();
+        let date = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+        let html = dir.join(html_filename("102".parse().unwrap(), date));
+        fs::write(&html, "<html></html>").unwrap();
+
+        let check = |ledger: &Ledger| {
+            let mv = |id: &str| ledger.status(id.parse().unwrap()).unwrap();
+            assert_eq!(ledger.moves().len(), 3);
+            assert_eq!(
+                ledger.date_span(),
+                Some((date, NaiveDate::from_ymd_opt(2019, 6, 3).unwrap()))
+            );
+            assert_eq!(
+                mv("101").export(ExportFormat::Gpx),
+                Some(Path::new("Move_2019_06_01_07_30_12_Running.gpx"))
+            );
+            assert_eq!(mv("101").html, None);
+            assert_eq!(mv("102").html.as_deref(), Some(html.as_path()));
+            assert!(mv("102").exports.is_empty());
+        };
+        let ledger = Ledger::load_in(&dir).unwrap();
+        check(&ledger);
+        ledger.save().unwrap();
+
+        // From now on, it's the ledger that's read.
+        fs::remove_file(dir.join(SAVED_MOVES_FILENAME)).unwrap();
+        check(&Ledger::load_in(&dir).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_failures_in_a_row_use_up_attempts() {
+        let date = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+        let mv = "101".parse().unwrap();
+        let mut ledger = Ledger::default();
+        ledger.merge(&[DatedMoves {
+            date,
+            moves: vec![mv],
+        }]);
+        let formats = [ExportFormat::Gpx];
+        let wanted = Wanted {
+            formats: &formats,
+            photos: false,
+            archive: false,
+        };
+
+        // Its page on one run, its export on the next and so on, each
+        // failing once first, is more than three attempts in all.
+        for _ in 0..5 {
+            assert_eq!(ledger.attempt(mv, &wanted, 3), Attempt::Try);
+            ledger.attempted(mv, &Err::<(), _>(anyhow::anyhow!("timed out")));
+            assert_eq!(ledger.attempt(mv, &wanted, 3), Attempt::Try);
+            ledger.attempted(mv, &Ok(()));
+        }
+        let status = ledger.status(mv).unwrap();
+        assert_eq!(status.attempts, 0);
+        assert_eq!(status.last_error, None);
+
+        for _ in 0..3 {
+            assert_eq!(ledger.attempt(mv, &wanted, 3), Attempt::Try);
+            ledger.attempted(mv, &Err::<(), _>(anyhow::anyhow!("timed out")));
+        }
+        assert_eq!(ledger.attempt(mv, &wanted, 3), Attempt::OutOfAttempts);
+        let status = ledger.status(mv).unwrap();
+        assert!(status.failed);
+        assert_eq!(status.last_error.as_deref(), Some("timed out"));
+        assert_eq!(ledger.attempt(mv, &wanted, 3), Attempt::Skip);
+    }
+}
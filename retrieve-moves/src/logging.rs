@@ -0,0 +1,162 @@
+use {
+    anyhow::{anyhow, Result},
+    indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle},
+    std::{
+        fs::File,
+        io::{self, IsTerminal, Write},
+        path::Path,
+        sync::Mutex,
+        time::Duration,
+    },
+    tracing_subscriber::{
+        filter::{LevelFilter, Targets},
+        fmt::format::FmtSpan,
+        fmt::MakeWriter,
+        prelude::*,
+        EnvFilter,
+    },
+};
+
+// What a run is up to goes to stderr through tracing, at --log-level
+// (or RUST_LOG), along with the session, day and move it's about.
+// Each WebDriver step (logging in, loading a page, exporting) is a
+// debug-level span, and --log-file writes everything, debug included,
+// as JSON lines with how long each step took, for working out
+// afterwards what went wrong.
+//
+// Underneath the log, progress bars show how many months of the
+// calendar have been walked and how many moves are done, with an ETA.
+// They're only drawn on a terminal.
+
+pub(crate) fn init(level: &str, log_file: Option<&Path>, progress: &Progress) -> Result<()> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(env) => EnvFilter::try_new(env),
+        Err(_) => EnvFilter::try_new(level),
+    }
+    .map_err(|e| anyhow!("bad log level: {}", e))?;
+    let console = tracing_subscriber::fmt::layer()
+        .without_time()
+        .with_target(false)
+        .with_ansi(io::stderr().is_terminal())
+        .with_writer(ProgressWriter(progress.multi.clone()))
+        .with_filter(filter);
+    let json = match log_file {
+        None => None,
+        Some(path) => {
+            let file = File::create(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_span_events(FmtSpan::CLOSE)
+                    .with_writer(Mutex::new(file))
+                    // The HTML parser's debug output would drown ours.
+                    .with_filter(
+                        Targets::new()
+                            .with_target(env!("CARGO_CRATE_NAME"), LevelFilter::DEBUG)
+                            .with_default(LevelFilter::INFO),
+                    ),
+            )
+        }
+    };
+    tracing_subscriber::registry()
+        .with(console)
+        .with(json)
+        .try_init()?;
+    Ok(())
+}
+
+// Writes the log above the progress bars rather than through them.
+struct ProgressWriter(MultiProgress);
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stderr().write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for ProgressWriter {
+    type Writer = ProgressWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        ProgressWriter(self.0.clone())
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Progress {
+    multi: MultiProgress,
+    months: ProgressBar,
+    moves: ProgressBar,
+}
+
+impl Progress {
+    pub(crate) fn new() -> Self {
+        Self::with_target(ProgressDrawTarget::stderr())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn hidden() -> Self {
+        Self::with_target(ProgressDrawTarget::hidden())
+    }
+
+    fn with_target(target: ProgressDrawTarget) -> Self {
+        // The bars are only drawn once they're started.
+        Self {
+            multi: MultiProgress::with_draw_target(target),
+            months: ProgressBar::hidden(),
+            moves: ProgressBar::hidden(),
+        }
+    }
+
+    // Going back from the last month until there are no more moves,
+    // we don't know how many months there'll be.
+    pub(crate) fn start_months(&self, months: Option<u64>) {
+        let months_bar = self.multi.add(self.months.clone());
+        match months {
+            Some(months) => {
+                months_bar.set_style(bar_style());
+                months_bar.set_length(months);
+            }
+            None => {
+                months_bar.set_style(
+                    ProgressStyle::with_template("{prefix:>6} {spinner} {pos} {msg}").unwrap(),
+                );
+                months_bar.enable_steady_tick(Duration::from_millis(250));
+            }
+        }
+        months_bar.set_prefix("months");
+    }
+
+    pub(crate) fn month_done(&self, year: i32, month: u32) {
+        self.months.set_message(format!("{}-{:02}", year, month));
+        self.months.inc(1);
+    }
+
+    pub(crate) fn start_moves(&self, moves: u64) {
+        self.months.finish();
+        let moves_bar = self.multi.add(self.moves.clone());
+        moves_bar.set_style(bar_style());
+        moves_bar.set_length(moves);
+        moves_bar.set_prefix("moves");
+    }
+
+    pub(crate) fn move_done(&self) {
+        self.moves.inc(1);
+    }
+
+    pub(crate) fn finish(&self) {
+        self.months.finish();
+        self.moves.finish();
+    }
+}
+
+fn bar_style() -> ProgressStyle {
+    ProgressStyle::with_template("{prefix:>6} [{bar:40}] {pos}/{len} ({eta} left) {msg}")
+        .unwrap()
+        .progress_chars("=> ")
+}
@@ -1,63 +1,342 @@
+mod archive;
+mod browser;
+mod credentials;
+mod date_range;
+mod download;
+mod export_format;
+mod export_index;
+mod failures;
+mod ledger;
+mod logging;
+mod move_page;
 mod move_scraper;
+mod photos;
+mod plan;
+mod shared;
+mod verify;
 
 use {
-    crate::move_scraper::{DatedMoves, Month, MoveScraper, Year},
-    anyhow::Result,
-    chrono::NaiveDate,
+    crate::{
+        browser::{BrowserConfig, BrowserKind},
+        credentials::Credentials,
+        date_range::Period,
+        export_format::ExportFormat,
+        ledger::{Attempt, Ledger, Wanted},
+        logging::Progress,
+        move_scraper::{Move, MoveScraper},
+        plan::Plan,
+        shared::Shared,
+    },
+    anyhow::{anyhow, bail, Result},
+    chrono::{Local, NaiveDate},
     std::{
-        env,
-        fs::File,
-        io::{ErrorKind, Read},
+        collections::{BTreeMap, VecDeque},
+        fs, io,
+        ops::RangeBounds,
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
     },
     structopt::StructOpt,
+    tracing::{error, info_span, warn},
 };
 
 fn main() -> Result<()> {
-    let name = env::var("MOVESCOUNT_NAME")?;
-    let password = env::var("MOVESCOUNT_PASSWORD")?;
     let opt = Opt::from_args();
+    let progress = Progress::new();
+    logging::init(&opt.log_level, opt.log_file.as_deref(), &progress)?;
+    let mut ledger = Ledger::load()?;
+    match opt.command {
+        Some(Command::Status) => {
+            ledger.print_status();
+            return Ok(());
+        }
+        Some(Command::Catalogue) => return move_page::write_catalogue(&ledger),
+        Some(Command::Verify { dry_run }) => {
+            return verify::verify(&mut ledger, &opt.download_dir, dry_run)
+        }
+        Some(Command::Plan { .. }) | None => {}
+    }
+    if opt.retry_failed {
+        ledger.clear_failures();
+    }
 
-    let mut dated_moves = saved_moves()?;
-    let mut scraper = MoveScraper::new(&name, &password)?;
-    // scraper.set_year_and_month(opt.year, opt.month)?;
-    // let mut dated_moves = scraper.moves_from_page()?;
-    // scraper.advance_month()?;
-    // dated_moves.extend(scraper.moves_from_page()?.into_iter());
+    // Without --from or --last, we start from the account's first move.
+    let range = date_range::bounds(
+        opt.from,
+        opt.to,
+        opt.before,
+        opt.last,
+        Local::now().date_naive(),
+        ledger.first_move(),
+    )?;
+    // A plan doesn't need to log in unless it's going to look at the
+    // calendar.
+    let plan = match opt.command {
+        Some(Command::Plan { json, offline }) => Some((json, offline)),
+        _ => None,
+    };
+    // With more than one session, each browser gets its own download
+    // directory, so that a session waiting for an export can't mistake
+    // another's for it.
+    if opt.sessions == 0 {
+        bail!("--sessions has to be at least 1");
+    }
+    let session_dirs = if opt.sessions == 1 {
+        vec![opt.download_dir.clone()]
+    } else {
+        (1..=opt.sessions)
+            .map(|k| opt.download_dir.join(format!("session-{}", k)))
+            .collect()
+    };
+    let shared = Arc::new(Shared::new(
+        ledger,
+        &opt.download_dir,
+        &session_dirs,
+        &opt.failures_dir,
+        Duration::from_secs(opt.min_interval),
+        progress.clone(),
+    )?);
+    if let Some((json, true)) = plan {
+        return print_plan(&opt, &shared, &range, json);
+    }
+    for dir in &session_dirs {
+        fs::create_dir_all(dir).map_err(|e| anyhow!("{}: {}", dir.display(), e))?;
+    }
+    let credentials =
+        Credentials::resolve(opt.credentials.as_deref(), opt.password_command.as_deref())?;
+    let connect = |download_dir: &Path| {
+        let browser_config = BrowserConfig {
+            webdriver_url: opt.webdriver_url.clone(),
+            browser: opt.browser,
+            headless: opt.headless,
+            download_dir: download_dir.to_path_buf(),
+            profile: opt.profile.clone(),
+        };
+        MoveScraper::new(
+            &credentials,
+            &browser_config,
+            Duration::from_secs(opt.download_timeout),
+            Arc::clone(&shared),
+        )
+    };
+    // The first session looks for the moves while the others are
+    // still to be started.
+    let mut scraper = connect(&session_dirs[0])?;
+    let scraped_moves = scraper.moves_for_range(&range)?;
+    if scraped_moves.is_empty() {
+        match shared.ledger().date_span() {
+            Some((first, last)) => warn!(
+                "no moves in that range, the moves we know of are from {} to {}",
+                first, last
+            ),
+            None => warn!("no moves in that range"),
+        }
+    }
+    shared.ledger().merge(&scraped_moves);
+    // The new moves aren't saved, so a plan really changes nothing.
+    if let Some((json, _)) = plan {
+        return print_plan(&opt, &shared, &range, json);
+    }
+    shared.ledger().save()?;
+    scraper.report_orphans();
 
-    // let start = NaiveDate::from_ymd(2020, 4, 15);
-    // let stop = NaiveDate::from_ymd(2020, 6, 15);
-    // let dated_moves = scraper.moves_for_range(&(start..stop));
+    // The moves still to be done, a day at a time, for the sessions to
+    // take from as they're ready.
+    let wanted = opt.wanted();
+    let queue = {
+        let ledger = shared.ledger();
+        let mut days = BTreeMap::<_, Vec<_>>::new();
+        for mv in ledger.moves() {
+            if let Some(status) = ledger
+                .status(mv)
+                .filter(|s| !s.failed && !s.is_done(&wanted))
+            {
+                days.entry(status.date).or_default().push(mv);
+            }
+        }
+        progress.start_moves(days.values().map(|moves| moves.len() as u64).sum());
+        Mutex::new(days.into_iter().collect::<VecDeque<_>>())
+    };
+    let failures = Mutex::new(Vec::new());
+    let errors = thread::scope(|scope| {
+        let mut scraper = Some(scraper);
+        let workers = session_dirs
+            .iter()
+            .enumerate()
+            .map(|(n, dir)| {
+                let (opt, shared, scraper, connect, queue, failures) =
+                    (&opt, &shared, scraper.take(), &connect, &queue, &failures);
+                scope.spawn(move || {
+                    let _session = info_span!("session", n = n + 1).entered();
+                    let connect = || connect(dir);
+                    let scraper = match scraper {
+                        Some(scraper) => scraper,
+                        None => connect()?,
+                    };
+                    work(opt, shared, scraper, connect, queue, failures)
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .zip(&session_dirs)
+            .filter_map(|(worker, dir)| match worker.join() {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(anyhow!("session in {}: {:#}", dir.display(), e)),
+                Err(_) => Some(anyhow!("session in {} panicked", dir.display())),
+            })
+            .collect::<Vec<_>>()
+    });
 
-    let start = NaiveDate::from_ymd(opt.year.into(), opt.month.into(), 1);
-    let scraped_moves = scraper.moves_for_range(&(start..))?;
-    move_scraper::merge(&mut dated_moves, &scraped_moves);
-    save_moves(&dated_moves)?;
-    // eprintln!("dated_moves: {:#?}", dated_moves);
-    let mut been_here = false;
-    for dmove in dated_moves {
-        scraper.save_html_moves(&dmove)?;
-        scraper.export_moves(&dmove, &mut been_here, opt.export)?;
+    progress.finish();
+    let failures = failures.into_inner().unwrap_or_else(|e| e.into_inner());
+    if !failures.is_empty() {
+        let ledger = shared.ledger();
+        error!("{} moves failed:", failures.len());
+        for mv in failures {
+            if let Some(status) = ledger.status(mv) {
+                error!(
+                    "  {} ({}): {}",
+                    mv,
+                    status.date,
+                    status.last_error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+    }
+    // The moves a session didn't get to are still in the ledger for
+    // next time, but the other sessions carry on without it.
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        None => Ok(()),
+        Some(first) => {
+            for e in errors {
+                error!("{:#}", e);
+            }
+            Err(first)
+        }
     }
-    Ok(())
 }
 
-const SAVED_MOVES_FILENAME: &str = "saved_moves.json";
-
-fn saved_moves() -> Result<Vec<DatedMoves>> {
-    match File::open(SAVED_MOVES_FILENAME) {
-        Ok(mut file) => {
-            let mut data = String::new();
-            file.read_to_string(&mut data)?;
-            serde_json::from_str(&data).map_err(|e| e.into())
+// One session's share of the work: it takes a day's moves at a time
+// until there are none left.  The ledger is saved after each attempt,
+// so that what we've done so far is remembered even if we die later
+// on.  A move that keeps failing is retried with a growing pause in
+// between (Movescount is slow and flaky, particularly for long moves)
+// until it's failed --max-attempts times in a row, and then it's
+// marked as failed and skipped until --retry-failed.
+fn work(
+    opt: &Opt,
+    shared: &Shared,
+    mut scraper: MoveScraper,
+    connect: impl Fn() -> Result<MoveScraper>,
+    queue: &Mutex<VecDeque<(NaiveDate, Vec<Move>)>>,
+    failures: &Mutex<Vec<Move>>,
+) -> Result<()> {
+    let wanted = opt.wanted();
+    loop {
+        let day = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+        let (date, moves) = match day {
+            Some(day) => day,
+            None => return Ok(()),
+        };
+        let _day = info_span!("day", %date).entered();
+        for mv in moves {
+            let _move = info_span!("move", id = %mv).entered();
+            let mut backoff = INITIAL_BACKOFF;
+            loop {
+                {
+                    let mut ledger = shared.ledger();
+                    match ledger.attempt(mv, &wanted, opt.max_attempts) {
+                        Attempt::Skip => break,
+                        Attempt::OutOfAttempts => {
+                            ledger.save()?;
+                            failures.lock().unwrap_or_else(|e| e.into_inner()).push(mv);
+                            break;
+                        }
+                        Attempt::Try => {}
+                    }
+                }
+                let result = scraper
+                    .save_html(mv)
+                    .and_then(|_| {
+                        if opt.photos {
+                            scraper.save_photos(mv)?;
+                        }
+                        // After the photos, which are then local.
+                        if opt.archive {
+                            scraper.archive_page(mv)?;
+                        }
+                        Ok(())
+                    })
+                    .and_then(|_| {
+                        opt.formats
+                            .iter()
+                            .try_for_each(|f| scraper.export(mv, *f, opt.export))
+                    });
+                let retrying = {
+                    let mut ledger = shared.ledger();
+                    ledger.attempted(mv, &result);
+                    ledger.save()?;
+                    ledger
+                        .status(mv)
+                        .is_some_and(|s| s.attempts < opt.max_attempts)
+                };
+                if let Err(e) = result {
+                    if retrying {
+                        warn!("failed: {:#}, retrying in {:?}", e, backoff);
+                        thread::sleep(backoff);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    } else {
+                        error!("failed: {:#}", e);
+                    }
+                    if scraper.is_alive() {
+                        scraper.forget_page();
+                    } else {
+                        warn!("WebDriver session died, starting a new one");
+                        scraper = connect()?;
+                    }
+                } else {
+                    // Not necessarily done, e.g., without --export, but
+                    // there's nothing more to do for it on this run.
+                    break;
+                }
+            }
+            shared.progress.move_done();
         }
-        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
-        Err(other) => Err(other.into()),
     }
 }
 
-fn save_moves(moves: &[DatedMoves]) -> Result<()> {
-    let mut file = File::create(SAVED_MOVES_FILENAME)?;
-    serde_json::to_writer(&mut file, moves).map_err(|e| e.into())
+fn print_plan<T: RangeBounds<NaiveDate>>(
+    opt: &Opt,
+    shared: &Shared,
+    range: &T,
+    json: bool,
+) -> Result<()> {
+    let ledger = shared.ledger();
+    let plan = Plan::new(&ledger, &shared.export_index(), &opt.formats, range);
+    if json {
+        serde_json::to_writer_pretty(io::stdout(), &plan)?;
+        println!();
+    } else {
+        plan.print();
+    }
+    Ok(())
+}
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+impl Opt {
+    fn wanted(&self) -> Wanted<'_> {
+        Wanted {
+            formats: if self.export { &self.formats } else { &[] },
+            photos: self.photos,
+            archive: self.archive,
+        }
+    }
 }
 
 #[derive(StructOpt, Debug)]
@@ -65,8 +344,105 @@ fn save_moves(moves: &[DatedMoves]) -> Result<()> {
 pub struct Opt {
     #[structopt(short = "e", long)]
     pub export: bool,
-    #[structopt(short = "y", long, default_value)]
-    year: Year,
-    #[structopt(short = "m", long, default_value)]
-    month: Month,
+    /// What --export exports: any of gpx, fit, kml, xlsx and tcx
+    #[structopt(long, use_delimiter = true, default_value = "gpx")]
+    formats: Vec<ExportFormat>,
+    /// Also save each move's photos, and point its saved page at them
+    #[structopt(long)]
+    photos: bool,
+    /// Also save the stylesheets, scripts and images that each saved
+    /// page uses in assets/, and point the page at them
+    #[structopt(long)]
+    archive: bool,
+    /// The first day to look for moves on [default: the first move]
+    #[structopt(long, conflicts_with = "last")]
+    from: Option<NaiveDate>,
+    /// The last day to look for moves on [default: today]
+    #[structopt(long, conflicts_with = "before")]
+    to: Option<NaiveDate>,
+    /// Look for moves before this day
+    #[structopt(long)]
+    before: Option<NaiveDate>,
+    /// Look for moves in the last so many days, weeks, months or
+    /// years, e.g., 30d, 6w, 3m or 1y
+    #[structopt(long)]
+    last: Option<Period>,
+    /// A JSON file with "name" and "password" or "password_command"
+    /// [default: ~/.config/retrieve-moves/credentials.json]
+    #[structopt(long, parse(from_os_str))]
+    credentials: Option<PathBuf>,
+    /// A command that prints the password, e.g., "pass show movescount"
+    #[structopt(long)]
+    password_command: Option<String>,
+    /// The WebDriver server, e.g., geckodriver or chromedriver
+    #[structopt(long, default_value = "http://localhost:4444")]
+    webdriver_url: String,
+    /// firefox or chrome
+    #[structopt(long, default_value)]
+    browser: BrowserKind,
+    /// Run the browser without a window
+    #[structopt(long)]
+    headless: bool,
+    /// A browser profile (Firefox) or user data directory (Chrome)
+    #[structopt(long, parse(from_os_str))]
+    profile: Option<PathBuf>,
+    /// Where the browser saves downloads
+    #[structopt(long, parse(from_os_str), default_value = ".")]
+    download_dir: PathBuf,
+    /// How many browsers to scrape with at once, each saving downloads
+    /// in its own session-N directory in --download-dir
+    #[structopt(long, default_value = "1")]
+    sessions: u32,
+    /// Seconds between page loads and exports, across all sessions
+    #[structopt(long, default_value = "1")]
+    min_interval: u64,
+    /// Where to save a screenshot, the URL and the DOM of the page
+    /// whenever a step fails
+    #[structopt(long, parse(from_os_str), default_value = "failures")]
+    failures_dir: PathBuf,
+    /// Seconds to wait for an export to finish downloading
+    #[structopt(long, default_value = "120")]
+    download_timeout: u64,
+    /// Failed attempts in a row at each move before it's marked as failed
+    #[structopt(long, default_value = "3")]
+    max_attempts: u32,
+    /// Try the moves that previously failed again
+    #[structopt(long)]
+    retry_failed: bool,
+    /// What to log: error, warn, info, debug or trace, or a filter
+    /// like RUST_LOG's, which overrides it
+    #[structopt(long, default_value = "info")]
+    log_level: String,
+    /// Also log everything, with how long each step took, to this
+    /// file as JSON lines
+    #[structopt(long, parse(from_os_str))]
+    log_file: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Summarise what's been saved and exported so far, and what's left
+    Status,
+    /// Write the start time of each saved move page to a JSON file,
+    /// and all of them to moves_catalogue.json
+    Catalogue,
+    /// List what's missing for each move in the range, without
+    /// downloading anything
+    Plan {
+        /// Print the plan as JSON
+        #[structopt(long)]
+        json: bool,
+        /// Only use the ledger, without looking for new moves
+        #[structopt(long)]
+        offline: bool,
+    },
+    /// Check that the GPX files are complete, and have the bad ones
+    /// exported again
+    Verify {
+        /// Only list what's wrong, without changing anything
+        #[structopt(long)]
+        dry_run: bool,
+    },
 }
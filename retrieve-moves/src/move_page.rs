@@ -0,0 +1,146 @@
+use {
+    crate::{
+        ledger::Ledger,
+        move_scraper::{Move, START_FORMAT},
+    },
+    anyhow::{anyhow, Result},
+    chrono::{NaiveDate, NaiveDateTime},
+    scraper::{ElementRef, Html, Selector},
+    serde::Serialize,
+    std::{
+        fs::{self, File},
+        io::Write,
+        path::Path,
+    },
+    tracing::{info, warn},
+};
+
+// Movescount is gone, so the move pages that save_html kept are our
+// only copy of what was on them.  This pulls what we can out of them
+// without a browser, writing Move_YYYY_MM_DD_<id>.json next to each
+// page and all of them together in moves_catalogue.json.
+//
+// For now that's only the start time, from the selector the scraper
+// has always read it with.  The activity, duration, distance, notes,
+// tags and so on are on the pages too, but their selectors need
+// taking from a real saved page, along with an excerpt of it for the
+// tests, before they can be trusted.
+
+const CATALOGUE_FILENAME: &str = "moves_catalogue.json";
+
+pub(crate) const START: &str = ".feed-content-top";
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct MoveInfo {
+    pub(crate) id: Move,
+    pub(crate) date: NaiveDate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) start: Option<NaiveDateTime>,
+}
+
+impl MoveInfo {
+    pub(crate) fn parse(id: Move, date: NaiveDate, html: &str) -> Result<Self> {
+        let page = Html::parse_document(html);
+        let root = page.root_element();
+        Ok(Self {
+            id,
+            date,
+            start: first_text(root, START)?
+                .and_then(|s| NaiveDateTime::parse_from_str(&s, START_FORMAT).ok()),
+        })
+    }
+
+    pub(crate) fn read(id: Move, date: NaiveDate, path: &Path) -> Result<Self> {
+        let html = fs::read_to_string(path)?;
+        Self::parse(id, date, &html)
+    }
+}
+
+// For the "catalogue" subcommand.  Pages that can't be read are
+// reported and skipped, so one bad page doesn't lose the rest.
+pub(crate) fn write_catalogue(ledger: &Ledger) -> Result<()> {
+    let mut catalogue = Vec::new();
+    for mv in ledger.moves() {
+        let status = match ledger.status(mv) {
+            Some(status) => status,
+            None => continue,
+        };
+        let html = match &status.html {
+            Some(html) => html,
+            None => continue,
+        };
+        match MoveInfo::read(mv, status.date, html) {
+            Err(e) => warn!("{}: {:#}", html.display(), e),
+            Ok(info) => {
+                let json = html.with_extension("json");
+                write_json(&json, &info)?;
+                catalogue.push(info);
+            }
+        }
+    }
+    write_json(Path::new(CATALOGUE_FILENAME), &catalogue)?;
+    info!("{} moves in {}", catalogue.len(), CATALOGUE_FILENAME);
+    Ok(())
+}
+
+pub(crate) fn write_json(path: &Path, value: &impl Serialize) -> Result<()> {
+    let mut file = File::create(path)?;
+    serde_json::to_writer_pretty(&mut file, value)?;
+    file.write_all(b"\n")?;
+    Ok(())
+}
+
+pub(crate) fn select<'a>(root: ElementRef<'a>, css: &str) -> Result<Vec<ElementRef<'a>>> {
+    let selector = Selector::parse(css).map_err(|e| anyhow!("{}: {:?}", css, e))?;
+    Ok(root.select(&selector).collect())
+}
+
+pub(crate) fn first_text(root: ElementRef, css: &str) -> Result<Option<String>> {
+    Ok(select(root, css)?
+        .into_iter()
+        .map(text)
+        .find(|s| !s.is_empty()))
+}
+
+// Points the attributes that are exactly one of the from URLs at the
+// matching to instead, e.g., at a local copy.  The page came from the
+// browser, which escapes the &s in attributes.
+pub(crate) fn rewrite_urls(html: &str, urls: &[(String, String)]) -> String {
+    let mut html = html.to_string();
+    for (from, to) in urls {
+        let to = format!("\"{}\"", to.replace('&', "&amp;"));
+        html = html.replace(&format!("\"{}\"", from.replace('&', "&amp;")), &to);
+        html = html.replace(&format!("\"{}\"", from), &to);
+    }
+    html
+}
+
+// Like a browser, collapse the whitespace in the page's source.
+pub(crate) fn text(elem: ElementRef) -> String {
+    elem.text()
+        .flat_map(|s| s.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_start_time_from_a_saved_move_page() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("move_101.html");
+        let date = NaiveDate::from_ymd_opt(2019, 6, 1).unwrap();
+        let info = MoveInfo::read("101".parse().unwrap(), date, &path).unwrap();
+        assert_eq!(
+            info,
+            MoveInfo {
+                id: "101".parse().unwrap(),
+                date,
+                start: Some(date.and_hms_opt(7, 30, 0).unwrap()),
+            }
+        );
+    }
+}
@@ -1,63 +1,145 @@
 use {
-    anyhow::{anyhow, bail, Result},
-    chrono::{
-        format::{DelayedFormat, StrftimeItems},
-        Datelike, Local, NaiveDate, NaiveDateTime, Timelike,
+    crate::{
+        archive,
+        browser::{
+            Browser, BrowserConfig, Element,
+            Locator::{self, Css, LinkText},
+        },
+        credentials::Credentials,
+        download::DownloadWatcher,
+        export_format::ExportFormat,
+        export_index, failures, ledger, move_page, photos,
+        shared::Shared,
     },
+    anyhow::{anyhow, bail, Error, Result},
+    chrono::{Datelike, Local, NaiveDate, NaiveDateTime, Timelike},
     serde::{Deserialize, Serialize},
     std::{
-        collections::HashMap,
         convert::{TryFrom, TryInto},
         fmt::{self, Display, Formatter},
-        fs::File,
+        fs::{self, File},
         io::Write,
-        num::NonZeroU8,
+        num::{NonZeroU8, ParseIntError},
         ops::{Bound, RangeBounds},
-        path::Path,
+        path::{Path, PathBuf},
         str::FromStr,
-        time::Duration,
-    },
-    thirtyfour_sync::{
-        prelude::*,
-        By::{Css, LinkText},
-        WebDriver,
+        sync::Arc,
+        time::{Duration, Instant},
     },
+    thirtyfour_sync::WebDriver,
+    tracing::{info, instrument, warn},
 };
 
-pub(crate) struct MoveScraper {
-    driver: WebDriver,
-    year_month: Option<(Year, Month)>,
+// How many months in a row without a move mean that we've gone back
+// past the first one.
+const EMPTY_MONTHS: u32 = 12;
+
+// How a move's page shows when it started.
+pub(crate) const START_FORMAT: &str = "%m/%d/%Y %H:%M";
+
+pub(crate) struct MoveScraper<B: Browser = WebDriver> {
+    driver: B,
+    year_month: Option<(i32, Month)>,
     current_move: Option<Move>,
+    download_dir: PathBuf,
+    download_timeout: Duration,
+    shared: Arc<Shared>,
 }
 
 impl MoveScraper {
-    pub(crate) fn new(name: &str, password: &str) -> WebDriverResult<Self> {
-        let caps = DesiredCapabilities::firefox();
-        let driver = WebDriver::new("http://localhost:4444", &caps)?;
-        driver.get("https://www.movescount.com/auth?redirect_uri=%2flatestmove")?;
-
-        driver.find_element(Css("#splEmail"))?.send_keys(name)?;
-        driver
-            .find_element(Css("#splPassword"))?
-            .send_keys(password)?;
-        driver.find_element(Css("#splLoginButton"))?.click()?;
+    pub(crate) fn new(
+        credentials: &Credentials,
+        config: &BrowserConfig,
+        download_timeout: Duration,
+        shared: Arc<Shared>,
+    ) -> Result<Self> {
+        let driver = config.connect()?;
+        let mut scraper = Self::with_browser(
+            driver,
+            config.download_dir.clone(),
+            download_timeout,
+            shared,
+        );
+        scraper
+            .log_in(credentials)
+            .map_err(|e| scraper.capture_failure("login", e))?;
+        Ok(scraper)
+    }
+}
 
-        // This is just to delay us until we know the authorization has worked.
-        // There's nothing special about Tools
-        driver.find_element(LinkText("Tools"))?;
-        Ok(Self {
+impl<B: Browser> MoveScraper<B> {
+    // download_dir is where this session's browser saves downloads.
+    // With more than one session, each has its own, and the exports
+    // are moved from there to the shared download directory.
+    pub(crate) fn with_browser(
+        driver: B,
+        download_dir: PathBuf,
+        download_timeout: Duration,
+        shared: Arc<Shared>,
+    ) -> Self {
+        Self {
             driver,
             year_month: None,
             current_move: None,
-        })
+            download_dir,
+            download_timeout,
+            shared,
+        }
+    }
+
+    #[instrument(level = "debug", skip_all)]
+    fn log_in(&mut self, credentials: &Credentials) -> Result<()> {
+        self.shared.rate_limit.wait();
+        self.driver
+            .get("https://www.movescount.com/auth?redirect_uri=%2flatestmove")?;
+
+        self.driver
+            .find(Css("#splEmail"))?
+            .send_keys(&credentials.name)?;
+        // Not passing on the error, in case it quotes what was typed.
+        self.driver
+            .find(Css("#splPassword"))?
+            .send_keys(&credentials.password)
+            .map_err(|_| anyhow!("couldn't type the password"))?;
+        self.driver.find(Css("#splLoginButton"))?.click()?;
+
+        // This is just to delay us until we know the authorization has worked.
+        // There's nothing special about Tools
+        self.driver.find(LinkText("Tools"))?;
+        Ok(())
+    }
+
+    // If geckodriver or Firefox has gone away, every request fails, so
+    // there's no point retrying without a new session.
+    pub(crate) fn is_alive(&self) -> bool {
+        self.driver.current_url().is_ok()
     }
 
-    pub(crate) fn set_year_and_month(&mut self, year: Year, month: Month) -> Result<()> {
+    // After a failure, we can't trust that we're on the page we think
+    // we're on.
+    pub(crate) fn forget_page(&mut self) {
+        self.year_month = None;
+        self.current_move = None;
+    }
+
+    // Saves what the browser was showing when step failed, for working
+    // out later what changed on Movescount, and passes the error on.
+    fn capture_failure(&self, step: &str, e: Error) -> Error {
+        match failures::capture(&self.driver, &self.shared.failures_dir, step, &e) {
+            Ok(path) => info!("saved the page for {} in {}", step, path.display()),
+            Err(capture_error) => warn!("couldn't save the page for {}: {:#}", step, capture_error),
+        }
+        e
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    pub(crate) fn set_year_and_month(&mut self, year: i32, month: Month) -> Result<()> {
         if self.year_month != Some((year, month)) {
             let url = format!(
                 "https://www.movescount.com/summary#calendar-month={}-{}&moves=",
                 year, month
             );
+            self.shared.rate_limit.wait();
             self.driver.get(&url)?;
             self.current_move = None;
 
@@ -76,18 +158,14 @@ impl MoveScraper {
             bail!("No year and month");
         }
 
-        let timeouts = self.driver.get_timeouts()?;
-        self.driver
-            .set_implicit_wait_timeout(Duration::from_millis(0))?;
-        let result = (|| {
+        self.driver.without_implicit_wait(|| {
             let mut ones_seen = 0;
 
             // We do the filter_map first, because it's more efficient, but
             // the code will read better if I break some of this into
             // a helper method
-            Ok(self
-                .driver
-                .find_elements(Css("div.calendar-day"))?
+            self.driver
+                .find_all(Css("div.calendar-day"))?
                 .into_iter()
                 .filter_map(|day| {
                     // We need to look at the date even if there are
@@ -100,88 +178,134 @@ impl MoveScraper {
                         Ok(d) => d,
                     };
 
-                    day.find_elements(Css("a.calendar-stack")).map_or_else(
-                        |err| Some(Err(err.into())),
-                        |elems| {
-                            if elems.is_empty() {
-                                None
-                            } else {
-                                Some(
-                                    elems
-                                        .into_iter()
-                                        .map(|e| e.try_into())
-                                        .collect::<Result<Vec<_>>>()
-                                        .map_or_else(Err, |moves| Ok(DatedMoves { date, moves })),
-                                )
-                            }
-                        },
-                    )
+                    self.driver
+                        .find_all_in(&day, Css("a.calendar-stack"))
+                        .map_or_else(
+                            |err| Some(Err(err)),
+                            |elems| {
+                                if elems.is_empty() {
+                                    None
+                                } else {
+                                    Some(
+                                        elems
+                                            .into_iter()
+                                            .map(|e| Move::from_element(&e))
+                                            .collect::<Result<Vec<_>>>()
+                                            .map_or_else(Err, |moves| {
+                                                Ok(DatedMoves { date, moves })
+                                            }),
+                                    )
+                                }
+                            },
+                        )
                 })
-                .collect::<Result<Vec<_>>>()?)
-        })();
-        self.driver.set_timeouts(timeouts)?;
-        result
+                .collect::<Result<Vec<_>>>()
+        })
     }
 
     pub(crate) fn moves_for_range<T: RangeBounds<NaiveDate>>(
         &mut self,
         range: &T,
     ) -> Result<Vec<DatedMoves>> {
-        // NOTE: Currently we disallow unbounded starts, but we could
-        // do something like look at the total number of moves and
-        // then look at all the months going backward until we find
-        // all the moves and then know where the first move is.  This
-        // is, however, throw-away code, so that seems silly.
         use Bound::*;
 
-        let start = match range.start_bound() {
-            Unbounded => bail!("Unbounded starts not supported"),
-            Included(start) | Excluded(start) => start,
-        };
         let year_month_top = match range.end_bound() {
             Unbounded => {
-                let today = Local::today();
-                NaiveDate::from_ymd(today.year(), today.month(), 1)
-            }
-            Included(start) | Excluded(start) => {
-                NaiveDate::from_ymd(start.year(), start.month(), 1)
+                let today = Local::now().date_naive();
+                today.with_day(1).unwrap()
             }
+            Included(start) | Excluded(start) => start.with_day(1).unwrap(),
+        };
+        let start = match range.start_bound() {
+            Unbounded => return self.moves_back_from(year_month_top, range),
+            Included(start) | Excluded(start) => start,
         };
         let mut results = Vec::new();
         let mut year;
         let mut month;
-        self.set_year_and_month(start.year().try_into()?, start.month().try_into()?)?;
+        let months = (year_month_top.year() - start.year()) * 12 + year_month_top.month() as i32
+            - start.month() as i32
+            + 1;
+        self.shared
+            .progress
+            .start_months(u64::try_from(months).ok());
+        self.set_year_and_month(start.year(), start.month().try_into()?)?;
         while {
-            let (y, m) = self.year_month.unwrap();
-            year = y.into();
-            month = m.into();
-            let current_year_month = NaiveDate::from_ymd(year, month, 1);
+            (year, month) = self.this_month();
+            let current_year_month = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
             current_year_month <= year_month_top
         } {
-            results.extend(self.moves_from_page()?.into_iter().filter(|m| {
+            let moves = self
+                .moves_from_page()
+                .map_err(|e| self.capture_failure(&format!("calendar {}-{:02}", year, month), e))?;
+            results.extend(moves.into_iter().filter(|m| {
                 let date = &m.date;
                 range.contains(date) && date.month() == month && date.year() == year
             }));
+            self.shared.progress.month_done(year, month);
             self.advance_month()?;
         }
         Ok(results)
     }
 
+    // Without a start, we don't know when the first move was, so we
+    // go backward a month at a time from the top until there've been
+    // EMPTY_MONTHS in a row without any moves, and the ledger
+    // remembers the first move so that later runs can start from it.
+    // An account that stopped being used long before the top will
+    // have lots of empty months first, so if the ledger knows of any
+    // moves, the empty months only count once we've seen one.  If it
+    // doesn't, they count from the top, so that an account without any
+    // moves stops, and an account that's been idle for longer needs
+    // --from the first time.
+    fn moves_back_from<T: RangeBounds<NaiveDate>>(
+        &mut self,
+        year_month_top: NaiveDate,
+        range: &T,
+    ) -> Result<Vec<DatedMoves>> {
+        let mut results = Vec::new();
+        self.shared.progress.start_months(None);
+        let mut counting = self.shared.ledger().date_span().is_none();
+        let mut first_move = None;
+        let mut empty_months = 0;
+        let (mut year, mut month) = (year_month_top.year(), year_month_top.month());
+        while empty_months < EMPTY_MONTHS {
+            self.set_year_and_month(year, month.try_into()?)?;
+            let this_month = self
+                .moves_from_page()
+                .map_err(|e| self.capture_failure(&format!("calendar {}-{:02}", year, month), e))?
+                .into_iter()
+                .filter(|m| m.date.year() == year && m.date.month() == month)
+                .collect::<Vec<_>>();
+            if this_month.is_empty() {
+                if counting {
+                    empty_months += 1;
+                }
+            } else {
+                counting = true;
+                empty_months = 0;
+                first_move = this_month.iter().map(|m| m.date).min();
+            }
+            results.extend(this_month.into_iter().filter(|m| range.contains(&m.date)));
+            self.shared.progress.month_done(year, month);
+            (year, month) = self.previous_month();
+        }
+        if let Some(date) = first_move {
+            self.shared.ledger().set_first_move(date);
+        }
+        results.sort_by_key(|m| m.date);
+        Ok(results)
+    }
+
+    #[instrument(level = "debug", skip(self))]
     pub(crate) fn advance_month(&mut self) -> Result<()> {
         if self.year_month.is_none() {
             bail!("No year and month");
         }
-        self.driver.find_element(Css(".icon-154"))?.click()?;
+        self.shared.rate_limit.wait();
+        self.driver.find(Css(".icon-154"))?.click()?;
         let (year, month) = self.next_month();
-        self.year_month = Some((year.try_into()?, month.try_into()?));
-        Ok(())
-    }
-
-    pub(crate) fn save_html_moves(&mut self, dmove: &DatedMoves) -> Result<()> {
-        let prefix = dmove.prefix();
-        for to_save in &dmove.moves {
-            self.save_html(*to_save, &prefix)?;
-        }
+        self.year_month = Some((year, month.try_into()?));
         Ok(())
     }
 
@@ -190,7 +314,7 @@ impl MoveScraper {
     // bits of the DOM that get changed in a way that prevents us from simply
     // using find_element or find_elements.
     fn update_year_month(&mut self) -> Result<()> {
-        let e = self.driver.find_element(Css("#calendarDate"))?;
+        let e = self.driver.find(Css("#calendarDate"))?;
         let mut retries_left = 30;
         let mut month_year;
         while {
@@ -205,127 +329,245 @@ impl MoveScraper {
         }
         let month_year_day = format!("{} 01", e.text()?);
         let date = NaiveDate::parse_from_str(&month_year_day, "%B %Y %d")?;
-        self.year_month = Some((date.year().try_into()?, date.month().try_into()?));
+        self.year_month = Some((date.year(), date.month().try_into()?));
         Ok(())
     }
 
-    fn save_html(&mut self, to_save: Move, prefix: &DelayedFormat<StrftimeItems>) -> Result<()> {
-        let filename = format!("{}{}.html", prefix, to_save.0);
-
-        if !Path::new(&filename).exists() {
-            self.goto_move(to_save)?;
-            let html = self.driver.find_element(Css("html"))?.outer_html()?;
+    // The shared ledger is only locked to look things up and to record
+    // them, never while the browser is busy.
+    #[instrument(level = "debug", skip(self))]
+    pub(crate) fn save_html(&mut self, to_save: Move) -> Result<()> {
+        let date = self
+            .shared
+            .ledger()
+            .status(to_save)
+            .ok_or_else(|| anyhow!("{} isn't in the ledger", to_save))?
+            .date;
+        let filename = PathBuf::from(ledger::html_filename(to_save, date));
+
+        let mut start = None;
+        if !filename.exists() {
+            let (started, html) = self
+                .page_html(to_save)
+                .map_err(|e| self.capture_failure(&format!("move{} html", to_save), e))?;
+            start = Some(started);
             File::create(&filename)?.write_all(html.as_bytes())?;
         }
+        if let Some(status) = self.shared.ledger().status_mut(to_save) {
+            if start.is_some() {
+                status.start = start;
+            }
+            status.html = Some(filename);
+        }
         Ok(())
     }
 
+    fn page_html(&mut self, mv: Move) -> Result<(NaiveDateTime, String)> {
+        self.goto_move(mv)?;
+        let start = self.start_time()?;
+        Ok((start, self.driver.find(Css("html"))?.outer_html()?))
+    }
+
+    #[instrument(level = "debug", skip(self))]
     fn goto_move(&mut self, to_goto: Move) -> Result<()> {
         if self.current_move != Some(to_goto) {
-            let url = format!("https://www.movescount.com/moves/move{}", to_goto);
-            self.driver.get(&url)?;
+            self.shared.rate_limit.wait();
+            self.driver.get(&move_url(to_goto))?;
             self.current_move = Some(to_goto);
             self.update_year_month()?;
         }
         Ok(())
     }
 
-    pub(crate) fn export_moves(
-        &mut self,
-        dmove: &DatedMoves,
-        been_here: &mut bool,
-        really_export: bool,
-    ) -> Result<()> {
-        let prefix = dmove.prefix();
-        let n = dmove.moves.len();
-        for to_export in &dmove.moves {
-            self.export(*to_export, &prefix, n, been_here, really_export)?;
+    // The page has to have been saved already.  The photos are fetched
+    // from whichever page the browser is on, which is always one of
+    // Movescount's, so the session's cookies go along.
+    #[instrument(level = "debug", skip(self))]
+    pub(crate) fn save_photos(&mut self, mv: Move) -> Result<()> {
+        let html = match self.shared.ledger().status(mv) {
+            None => bail!("{} isn't in the ledger", mv),
+            Some(status) if status.photos.is_some() => return Ok(()),
+            Some(status) => status
+                .html
+                .clone()
+                .ok_or_else(|| anyhow!("{}'s page hasn't been saved", mv))?,
+        };
+        let saved =
+            photos::save_photos(&self.driver, &move_url(mv), &html, &self.shared.rate_limit)?;
+        if !saved.is_empty() {
+            info!("saved {} photos", saved.len());
+        }
+        if let Some(status) = self.shared.ledger().status_mut(mv) {
+            status.photos = Some(saved);
+        }
+        Ok(())
+    }
+
+    // Like the photos, the assets are fetched from whichever Movescount
+    // page the browser is on.
+    #[instrument(level = "debug", skip(self))]
+    pub(crate) fn archive_page(&mut self, mv: Move) -> Result<()> {
+        let html = match self.shared.ledger().status(mv) {
+            None => bail!("{} isn't in the ledger", mv),
+            Some(status) if status.archived => return Ok(()),
+            Some(status) => status
+                .html
+                .clone()
+                .ok_or_else(|| anyhow!("{}'s page hasn't been saved", mv))?,
+        };
+        archive::archive_page(&self.driver, &move_url(mv), &html, &self.shared.rate_limit)?;
+        if let Some(status) = self.shared.ledger().status_mut(mv) {
+            status.archived = true;
         }
         Ok(())
     }
 
-    fn export(
+    // Only the hour and minute are shown on the move's page.
+    fn start_time(&self) -> Result<NaiveDateTime> {
+        Ok(NaiveDateTime::parse_from_str(
+            &self.driver.find(Css(move_page::START))?.text()?,
+            START_FORMAT,
+        )?)
+    }
+
+    // All of a day's moves are exported by the same session, so that
+    // the counting below isn't thrown off by another session's
+    // download of a move on that day.
+    pub(crate) fn export(
         &mut self,
         to_export: Move,
-        prefix: &DelayedFormat<StrftimeItems>,
-        n: usize,
-        been_here: &mut bool,
+        format: ExportFormat,
         really_export: bool,
     ) -> Result<()> {
-        let fileglob = format!("{}??_??_??*.gpx", prefix);
-
-        let paths = glob::glob(&fileglob)?
-            .map(|r| r.map_err(|e| e.into()))
-            .collect::<Result<Vec<_>>>()?;
-        let count = paths.len();
+        let (date, n) = {
+            let ledger = self.shared.ledger();
+            match ledger.status(to_export) {
+                None => bail!("{} isn't in the ledger", to_export),
+                Some(status) if status.export(format).is_some_and(|path| path.exists()) => {
+                    return Ok(())
+                }
+                Some(status) => (status.date, ledger.count_on(status.date)),
+            }
+        };
+        let count = self.shared.export_index().count_for_date(format, date);
 
         if count > n {
             bail!(
-                "Found {} gpx files, but only know of {} moves, prefix: {}",
+                "Found {} {} files, but only know of {} moves, date: {}",
                 count,
+                format,
                 n,
-                prefix
+                date
             );
         }
 
         if n > count {
             if !really_export {
-                eprintln!("Not trying to export {}:{}", prefix, to_export);
+                info!("not trying to export {}:{} as {}", date, to_export, format);
                 return Ok(());
             }
-            self.goto_move(to_export)?;
-            if self.gpx_file_is_already_present(prefix)? {
-                return Ok(());
-            }
-            let tools = self.driver.find_element(LinkText("Tools"))?;
-            self.driver
-                .action_chain()
-                .move_to_element_center(&tools)
-                .perform()?;
-            std::thread::sleep(Duration::from_secs(5)); // New, only used after
-                                                        // I scraped 2,228 moves.  Not sure that this helps, but it appears
-                                                        // that my longer moves haven't downloaded previously.
-            self.driver
-                .find_element(LinkText("Export as GPX"))?
-                .click()?;
-            let duration = if *been_here {
-                4 // I used to use 2, but I'm getting more delay now
-            } else {
-                *been_here = true;
-                10
-            };
-            std::thread::sleep(Duration::from_secs(duration));
+            self.export_now(to_export, format, date).map_err(|e| {
+                self.capture_failure(&format!("move{} export {}", to_export, format), e)
+            })?;
         }
         Ok(())
     }
 
+    fn export_now(&mut self, to_export: Move, format: ExportFormat, date: NaiveDate) -> Result<()> {
+        self.goto_move(to_export)?;
+        let start = self.start_time()?;
+        if let Some(status) = self.shared.ledger().status_mut(to_export) {
+            status.start = Some(start);
+        }
+        if let Some(path) = self.already_exported(format, date, start) {
+            return self.record_export(to_export, format, &path);
+        }
+        let path = self.download(format)?;
+        info!("exported {}:{} to {}", date, to_export, path.display());
+        if !self.shared.export_index().add(&path) {
+            warn!("{} isn't named like a move", path.display());
+        }
+        self.record_export(to_export, format, &path)
+    }
+
+    // Exports the move we're on from its Tools menu and waits for the
+    // file to be downloaded.
+    #[instrument(level = "debug", skip(self))]
+    fn download(&self, format: ExportFormat) -> Result<PathBuf> {
+        let watcher = DownloadWatcher::new(&self.download_dir, self.download_timeout)?;
+        {
+            let tools = self.driver.find(LinkText("Tools"))?;
+            self.driver.hover(&tools)?;
+            let link = self.wait_until_displayed(LinkText(format.link_text()))?;
+            self.shared.rate_limit.wait();
+            link.click()?;
+        }
+        watcher.wait_for(format)
+    }
+
+    // Once we know which move an exported file belongs to, we put the
+    // move's id in its name, so that two moves that start in the same
+    // minute can't be confused, and remember it in the ledger.  A file
+    // in a session's own download directory is moved to the shared
+    // one at the same time.
+    fn record_export(&self, to_export: Move, format: ExportFormat, path: &Path) -> Result<()> {
+        let named = export_index::with_move_id(path, to_export.0);
+        let named = match named.file_name() {
+            Some(name) => self.shared.download_dir.join(name),
+            None => named,
+        };
+        if named != path {
+            fs::rename(path, &named)?;
+            self.shared.export_index().rename(path, &named);
+        }
+        if let Some(status) = self.shared.ledger().status_mut(to_export) {
+            status.exports.insert(format, named);
+        }
+        Ok(())
+    }
+
+    // The Tools menu appears when hovered over, but not instantly.
+    fn wait_until_displayed(&self, locator: Locator) -> Result<B::Element<'_>> {
+        let start = Instant::now();
+        loop {
+            if let Ok(elem) = self.driver.find(locator) {
+                if elem.is_displayed()? {
+                    return Ok(elem);
+                }
+            }
+            if start.elapsed() > self.download_timeout {
+                bail!("{} never appeared", locator);
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
+    }
+
     // This only works after we've visited a particular move, because
     // we have to extract the hour and minute from the page in order
-    // for our glob to be sufficiently narrow to prevent false
+    // for our lookup to be sufficiently narrow to prevent false
     // positives.  FWIW, there's no way we can get the starting
-    // second, so we still have to glob, which means that we'll run
-    // into trouble if there are two legitimate moves that both start
-    // on the same minute.  I don't think I'll ever have such moves
-    // though.
-    fn gpx_file_is_already_present(
-        &mut self,
-        prefix: &DelayedFormat<StrftimeItems>,
-    ) -> Result<bool> {
-        let start = NaiveDateTime::parse_from_str(
-            &self.driver.find_element(Css(".feed-content-top"))?.text()?,
-            "%m/%d/%Y %H:%M",
-        )?;
-        let fileglob = format!(
-            "{}{:02}_{:02}_??*.gpx",
-            prefix,
-            start.hour(),
-            start.minute()
-        );
-        match glob::glob(&fileglob)?.next() {
-            None => Ok(false),
-            Some(Ok(_)) => Ok(true),
-            Some(Err(e)) => Err(e.into()),
-        }
+    // second, but files that are already known to belong to another
+    // move are skipped, so two moves that start in the same minute
+    // only cause trouble for files downloaded before we started
+    // putting the move id in the name.
+    fn already_exported(
+        &self,
+        format: ExportFormat,
+        date: NaiveDate,
+        start: NaiveDateTime,
+    ) -> Option<PathBuf> {
+        let ledger = self.shared.ledger();
+        self.shared
+            .export_index()
+            .find(format, date, start.hour(), start.minute())
+            .iter()
+            .find(|path| !ledger.is_known_export(path))
+            .cloned()
+    }
+
+    pub(crate) fn report_orphans(&self) {
+        let ledger = self.shared.ledger();
+        self.shared.export_index().report_orphans(&ledger);
     }
 
     // ones_seen is used to deal with calendar dates from the previous
@@ -333,13 +575,14 @@ impl MoveScraper {
     // the first of the month, we're on the previous month.  Once
     // we've seen two first of months, the second is in the next
     // month.
-    fn date_from_calendar_day(
-        &self,
+    fn date_from_calendar_day<'a>(
+        &'a self,
         ones_seen: &mut u8,
-        calendar_day: &WebElement,
+        calendar_day: &B::Element<'a>,
     ) -> Result<NaiveDate> {
-        let day = calendar_day
-            .find_element(Css(".calendar-day-number"))?
+        let day = self
+            .driver
+            .find_in(calendar_day, Css(".calendar-day-number"))?
             .text()?
             .parse()?;
         if day == 1 {
@@ -351,9 +594,8 @@ impl MoveScraper {
             2 => self.next_month(),
             n => bail!("Seen {} first days of the month", n),
         };
-        // NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| anyhow!("impossible date, year: {}, month: {}, day: {}, *ones_seen: {}", year, month, day, *ones_seen))
+        // The page is saved in --failures-dir when this fails.
         NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| {
-            std::thread::sleep(Duration::from_secs(500));
             anyhow!(
                 "impossible date, year: {}, month: {}, day: {}, *ones_seen: {}",
                 year,
@@ -375,7 +617,7 @@ impl MoveScraper {
 
     fn this_month(&self) -> (i32, u32) {
         let (year, month) = self.year_month.unwrap();
-        (year.into(), month.into())
+        (year, month.into())
     }
 
     fn next_month(&self) -> (i32, u32) {
@@ -388,22 +630,16 @@ impl MoveScraper {
     }
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
-pub(crate) struct Move(u32);
-
-impl Move {}
-
-impl Display for Move {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        self.0.fmt(f)
-    }
+fn move_url(mv: Move) -> String {
+    format!("https://www.movescount.com/moves/move{}", mv)
 }
 
-impl TryFrom<WebElement<'_>> for Move {
-    type Error = anyhow::Error;
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub(crate) struct Move(u32);
 
-    fn try_from(e: WebElement) -> Result<Self> {
-        e.get_attribute("data-id")?.map_or_else(
+impl Move {
+    fn from_element(e: &impl Element) -> Result<Self> {
+        e.attribute("data-id")?.map_or_else(
             || bail!("no data-id"),
             |id| {
                 id.strip_prefix("move-")
@@ -414,91 +650,29 @@ impl TryFrom<WebElement<'_>> for Move {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub(crate) struct DatedMoves {
-    date: NaiveDate,
-    moves: Vec<Move>,
-}
-
-impl DatedMoves {
-    fn prefix(&self) -> DelayedFormat<StrftimeItems> {
-        self.date.format("Move_%Y_%m_%d_")
-    }
-}
-
-// Making range limited Year and Month was an experiment.  Turns out
-// it's lots of boilerplate.  There's probably a better way to do it.
-
-#[derive(Debug)]
-pub(crate) enum ParseYearError {
-    NotNumeric,
-    OutOfRange,
-}
-
-impl Display for ParseYearError {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        use ParseYearError::*;
-
-        match self {
-            NotNumeric => write!(f, "must be numeric"),
-            OutOfRange => write!(f, "must be >= {} and <= {}", Year::MIN_YEAR, Year::MAX_YEAR),
-        }
-    }
-}
-
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-pub(crate) struct Year(u8);
-
-impl Year {
-    const MIN_YEAR: u16 = 2010;
-    const MAX_YEAR: u16 = 2021;
-
-    fn new(value: u16) -> Option<Self> {
-        if !(Self::MIN_YEAR..=Self::MAX_YEAR).contains(&value) {
-            return None;
-        }
-        let u: Option<u8> = (value - Self::MIN_YEAR).try_into().ok();
-        u.map(Self)
-    }
-}
-
-impl Default for Year {
-    fn default() -> Self {
-        Self::new(2013).unwrap()
-    }
-}
-
-impl Display for Year {
+impl Display for Move {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        (Into::<u8>::into(self.0) as u16 + Self::MIN_YEAR).fmt(f)
+        self.0.fmt(f)
     }
 }
 
-impl FromStr for Year {
-    type Err = ParseYearError;
+impl FromStr for Move {
+    type Err = ParseIntError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        use ParseYearError::*;
-
-        s.parse()
-            .map_err(|_| NotNumeric)
-            .and_then(|year| Self::new(year).ok_or(OutOfRange))
+        s.parse().map(Self)
     }
 }
 
-impl From<Year> for i32 {
-    fn from(year: Year) -> Self {
-        (year.0 as u16 + Year::MIN_YEAR).into()
-    }
+#[derive(Debug)]
+pub(crate) struct DatedMoves {
+    pub(crate) date: NaiveDate,
+    pub(crate) moves: Vec<Move>,
 }
 
-impl TryFrom<i32> for Year {
-    type Error = anyhow::Error;
-
-    fn try_from(value: i32) -> Result<Self> {
-        Self::new(value.try_into()?).ok_or_else(|| anyhow!("year out of range"))
-    }
-}
+// Making a range limited Month was an experiment.  Turns out it's
+// lots of boilerplate.  There's probably a better way to do it.  (Year
+// used to be limited to 2010-2021, which only got in the way.)
 
 #[derive(Debug)]
 pub(crate) enum ParseMonthError {
@@ -537,12 +711,6 @@ impl Month {
     }
 }
 
-impl Default for Month {
-    fn default() -> Self {
-        Self::new(11).unwrap()
-    }
-}
-
 impl Display for Month {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         self.0.fmt(f)
@@ -575,19 +743,173 @@ impl TryFrom<u32> for Month {
     }
 }
 
-pub(crate) fn merge(dest: &mut Vec<DatedMoves>, src: &[DatedMoves]) {
-    let mut h = HashMap::new();
-    h.extend(dest.iter().map(|dm| (dm.date, dm.moves.clone())));
-    for DatedMoves { date, moves } in src {
-        let v = h.entry(*date).or_insert_with(Vec::new);
-        for to_merge in moves {
-            if !v.contains(to_merge) {
-                v.push(*to_merge);
-            }
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            browser::fixture::{failures_dir, FixtureBrowser},
+            ledger::Ledger,
+            logging::Progress,
+        },
+    };
+
+    const JUNE: &str = "https://www.movescount.com/summary#calendar-month=2019-6&moves=";
+    const JULY: &str = "https://www.movescount.com/summary#calendar-month=2019-7&moves=";
+    const MOVE_101: &str = "https://www.movescount.com/moves/move101";
+
+    fn scraper(browser: FixtureBrowser) -> MoveScraper<FixtureBrowser> {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+        let shared = Shared::new(
+            Ledger::default(),
+            &fixtures,
+            &[],
+            &failures_dir(),
+            Duration::ZERO,
+            Progress::hidden(),
+        )
+        .unwrap();
+        MoveScraper::with_browser(browser, fixtures, Duration::from_secs(1), Arc::new(shared))
+    }
+
+    fn calendar() -> MoveScraper<FixtureBrowser> {
+        scraper(
+            FixtureBrowser::new()
+                .page(JUNE, "calendar_2019_06.html")
+                .unwrap()
+                .page(JULY, "calendar_2019_07.html")
+                .unwrap(),
+        )
+    }
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn dates_and_ids(dated_moves: &[DatedMoves]) -> Vec<(NaiveDate, Vec<u32>)> {
+        dated_moves
+            .iter()
+            .map(|dm| (dm.date, dm.moves.iter().map(|mv| mv.0).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn update_year_month_reads_the_calendar_heading() {
+        let mut scraper = calendar();
+        scraper.driver.get(JULY).unwrap();
+        scraper.update_year_month().unwrap();
+        assert_eq!(scraper.this_month(), (2019, 7));
+    }
+
+    #[test]
+    fn moves_from_page_dates_the_days_of_neighbouring_months() {
+        let mut scraper = calendar();
+        scraper
+            .set_year_and_month(2019, 6.try_into().unwrap())
+            .unwrap();
+        assert_eq!(
+            dates_and_ids(&scraper.moves_from_page().unwrap()),
+            vec![
+                (ymd(2019, 5, 28), vec![100]),
+                (ymd(2019, 6, 1), vec![101]),
+                (ymd(2019, 6, 15), vec![102, 103]),
+                (ymd(2019, 7, 2), vec![104]),
+            ]
+        );
+    }
+
+    #[test]
+    fn moves_for_range_follows_the_next_month_link() {
+        let mut scraper = calendar();
+        let moves = scraper
+            .moves_for_range(&(ymd(2019, 6, 10)..=ymd(2019, 7, 31)))
+            .unwrap();
+        // The move on July 2nd is on both pages, but only counted once.
+        assert_eq!(
+            dates_and_ids(&moves),
+            vec![
+                (ymd(2019, 6, 15), vec![102, 103]),
+                (ymd(2019, 7, 2), vec![104]),
+                (ymd(2019, 7, 20), vec![105]),
+            ]
+        );
+        assert_eq!(scraper.this_month(), (2019, 8));
+    }
+
+    #[test]
+    fn moves_for_range_finds_the_first_move_without_a_start() {
+        let mut browser = FixtureBrowser::new()
+            .page(JUNE, "calendar_2019_06.html")
+            .unwrap()
+            .page(JULY, "calendar_2019_07.html")
+            .unwrap();
+        // May 2019 back to June 2018
+        for (year, month) in (1..=5)
+            .map(|m| (2019, m))
+            .chain((6..=12).map(|m| (2018, m)))
+        {
+            let url = format!(
+                "https://www.movescount.com/summary#calendar-month={}-{}&moves=",
+                year, month
+            );
+            browser = browser.page(&url, "calendar_empty.html").unwrap();
         }
+        let mut scraper = scraper(browser);
+        let moves = scraper.moves_for_range(&(..=ymd(2019, 7, 31))).unwrap();
+        assert_eq!(
+            dates_and_ids(&moves),
+            vec![
+                (ymd(2019, 6, 1), vec![101]),
+                (ymd(2019, 6, 15), vec![102, 103]),
+                (ymd(2019, 7, 2), vec![104]),
+                (ymd(2019, 7, 20), vec![105]),
+            ]
+        );
+        // Twelve empty months, and no further.
+        assert_eq!(scraper.this_month(), (2018, 6));
+        assert_eq!(scraper.shared.ledger().first_move(), Some(ymd(2019, 6, 1)));
+    }
+
+    #[test]
+    fn moves_from_page_rejects_stacks_that_arent_moves() {
+        let mut scraper = scraper(
+            FixtureBrowser::new()
+                .page(JUNE, "calendar_bad_move_id.html")
+                .unwrap(),
+        );
+        scraper
+            .set_year_and_month(2019, 6.try_into().unwrap())
+            .unwrap();
+        assert!(scraper.moves_from_page().is_err());
+    }
+
+    #[test]
+    fn a_calendar_that_cant_be_read_is_captured() {
+        let mut scraper = scraper(
+            FixtureBrowser::new()
+                .page(JUNE, "calendar_bad_move_id.html")
+                .unwrap(),
+        );
+        assert!(scraper
+            .moves_for_range(&(ymd(2019, 6, 1)..=ymd(2019, 6, 30)))
+            .is_err());
+        let dir = scraper.shared.failures_dir.clone();
+        assert!(dir.join("calendar-2019-06.json").exists());
+        assert!(dir.join("calendar-2019-06.html").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn start_time_comes_from_the_move_page() {
+        let mut scraper = scraper(
+            FixtureBrowser::new()
+                .page(MOVE_101, "move_101.html")
+                .unwrap(),
+        );
+        scraper.goto_move(Move(101)).unwrap();
+        assert_eq!(
+            scraper.start_time().unwrap(),
+            ymd(2019, 6, 1).and_hms_opt(7, 30, 0).unwrap()
+        );
     }
-    *dest = h
-        .into_iter()
-        .map(|(date, moves)| DatedMoves { date, moves })
-        .collect::<Vec<_>>();
 }
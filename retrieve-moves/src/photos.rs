@@ -0,0 +1,204 @@
+use {
+    crate::{
+        browser::Browser,
+        move_page::{self, select},
+        shared::RateLimit,
+    },
+    anyhow::{anyhow, Result},
+    scraper::Html,
+    serde::Serialize,
+    std::{
+        fs,
+        path::{Path, PathBuf},
+    },
+    url::Url,
+};
+
+// With --photos, a move's photos are saved in a directory named after
+// its page, e.g., Move_2019_06_01_101/, with photos.json listing the
+// URL each one came from.  The page is then rewritten to show the
+// saved copies, so that it still works once Movescount is gone.
+//
+// These selectors haven't been checked against a real page.  If they
+// don't match, a move's photos are missed and it's recorded as having
+// none.  Captions and geotags aren't read at all until there's a real
+// page to take their selectors from.
+
+const PHOTOS: &str = ".move-photos .photo";
+const FULL_SIZE: &str = "a";
+const THUMBNAIL: &str = "img";
+
+const PHOTOS_FILENAME: &str = "photos.json";
+const EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "gif", "webp"];
+
+// The URLs are as they appear in the page, which may be relative.
+#[derive(Debug, PartialEq)]
+struct Photo {
+    url: String,
+    thumbnail: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SavedPhoto {
+    file: String,
+    url: String,
+}
+
+fn photos(html: &str) -> Result<Vec<Photo>> {
+    let page = Html::parse_document(html);
+    let mut photos = Vec::new();
+    for elem in select(page.root_element(), PHOTOS)? {
+        let href = select(elem, FULL_SIZE)?
+            .into_iter()
+            .find_map(|a| a.value().attr("href"));
+        let src = select(elem, THUMBNAIL)?
+            .into_iter()
+            .find_map(|img| img.value().attr("src"));
+        // Without a link to the full size photo, the thumbnail will
+        // have to do.
+        let (url, thumbnail) = match (href, src) {
+            (Some(href), src) => (href, src),
+            (None, Some(src)) => (src, None),
+            (None, None) => continue,
+        };
+        photos.push(Photo {
+            url: url.to_string(),
+            thumbnail: thumbnail.map(|s| s.to_string()),
+        });
+    }
+    Ok(photos)
+}
+
+// Returns the saved photos, none if the page doesn't have any.
+// page_url is where the page came from, for the photos' relative URLs.
+pub(crate) fn save_photos<B: Browser>(
+    browser: &B,
+    page_url: &str,
+    html_path: &Path,
+    rate_limit: &RateLimit,
+) -> Result<Vec<PathBuf>> {
+    let html = fs::read_to_string(html_path)?;
+    let photos = photos(&html)?;
+    let dir = html_path.with_extension("");
+    let dir_name = dir
+        .file_name()
+        .ok_or_else(|| anyhow!("{}: not a page", html_path.display()))?
+        .to_string_lossy()
+        .into_owned();
+    // A page that's already been rewritten has nothing left to fetch.
+    let prefix = format!("{}/", dir_name);
+    if photos.iter().all(|photo| photo.url.starts_with(&prefix)) {
+        let parent = html_path.parent().unwrap_or_else(|| Path::new(""));
+        return Ok(photos.iter().map(|photo| parent.join(&photo.url)).collect());
+    }
+
+    fs::create_dir_all(&dir)?;
+    let base = Url::parse(page_url)?;
+    let mut saved = Vec::new();
+    let mut rewrites = Vec::new();
+    let mut paths = Vec::new();
+    for (i, photo) in photos.iter().enumerate() {
+        let url = base.join(&photo.url)?;
+        let file = format!("photo_{}.{}", i + 1, extension(&url));
+        rate_limit.wait();
+        let data = browser.fetch(url.as_str())?;
+        let path = dir.join(&file);
+        fs::write(&path, data).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        paths.push(path);
+
+        let local = format!("{}{}", prefix, file);
+        rewrites.push((photo.url.clone(), local.clone()));
+        if let Some(thumbnail) = &photo.thumbnail {
+            rewrites.push((thumbnail.clone(), local));
+        }
+        saved.push(SavedPhoto {
+            file,
+            url: url.to_string(),
+        });
+    }
+    if !saved.is_empty() {
+        move_page::write_json(&dir.join(PHOTOS_FILENAME), &saved)?;
+        fs::write(html_path, move_page::rewrite_urls(&html, &rewrites))?;
+    }
+    Ok(paths)
+}
+
+// Photos whose URLs don't say what they are are most likely JPEGs.
+fn extension(url: &Url) -> String {
+    Path::new(url.path())
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .filter(|ext| EXTENSIONS.contains(&ext.as_str()))
+        .unwrap_or_else(|| "jpg".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::browser::fixture::{fixture_path, FixtureBrowser},
+        std::{process, time::Duration},
+    };
+
+    const PAGE_URL: &str = "https://www.movescount.com/moves/move101";
+
+    #[test]
+    fn finds_photos_and_their_thumbnails() {
+        let html = fs::read_to_string(fixture_path("move_101.html")).unwrap();
+        assert_eq!(
+            photos(&html).unwrap(),
+            vec![
+                Photo {
+                    url: "https://photos.movescount.com/101/summit.jpg".to_string(),
+                    thumbnail: Some(
+                        "https://photos.movescount.com/101/summit_thumb.jpg".to_string()
+                    ),
+                },
+                Photo {
+                    url: "/photos/101/creek.png?size=full&v=2".to_string(),
+                    thumbnail: Some("/photos/101/creek_thumb.png".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn saves_photos_and_points_the_page_at_them() {
+        let dir = std::env::temp_dir().join(format!("retrieve-moves-photos-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let html_path = dir.join("Move_2019_06_01_101.html");
+        fs::copy(fixture_path("move_101.html"), &html_path).unwrap();
+        let browser = FixtureBrowser::new()
+            .resource(
+                "https://photos.movescount.com/101/summit.jpg",
+                "photo_summit.jpg",
+            )
+            .resource(
+                "https://www.movescount.com/photos/101/creek.png?size=full&v=2",
+                "photo_creek.png",
+            );
+        let rate_limit = RateLimit::new(Duration::ZERO);
+
+        let photo_dir = dir.join("Move_2019_06_01_101");
+        let expected = vec![photo_dir.join("photo_1.jpg"), photo_dir.join("photo_2.png")];
+        let saved = save_photos(&browser, PAGE_URL, &html_path, &rate_limit).unwrap();
+        assert_eq!(saved, expected);
+        assert_eq!(
+            fs::read(&saved[1]).unwrap(),
+            fs::read(fixture_path("photo_creek.png")).unwrap()
+        );
+        let html = fs::read_to_string(&html_path).unwrap();
+        assert!(html.contains(r#"<img src="Move_2019_06_01_101/photo_1.jpg">"#));
+        assert!(html.contains(r#"<a href="Move_2019_06_01_101/photo_2.png">"#));
+        assert!(!html.contains("creek"));
+        let json = fs::read_to_string(photo_dir.join(PHOTOS_FILENAME)).unwrap();
+        assert!(json.contains(
+            "\"url\": \"https://www.movescount.com/photos/101/creek.png?size=full&v=2\""
+        ));
+
+        // The second time around, there's nothing to fetch.
+        let again = save_photos(&FixtureBrowser::new(), PAGE_URL, &html_path, &rate_limit);
+        assert_eq!(again.unwrap(), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
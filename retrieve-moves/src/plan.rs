@@ -0,0 +1,288 @@
+use {
+    crate::{
+        export_format::ExportFormat,
+        export_index::ExportIndex,
+        ledger::{self, Ledger, MoveStatus},
+        move_scraper::Move,
+    },
+    chrono::{NaiveDate, Timelike},
+    serde::Serialize,
+    std::{collections::BTreeMap, ops::RangeBounds, path::Path},
+};
+
+// What a run would do, without doing it: for each move in the range,
+// whether its HTML and each export are already done, already
+// downloaded but not yet in the ledger (which a run would just
+// record), or missing.  It's worked out the same way that save_html
+// and export decide whether there's anything to do.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Need {
+    Done,
+    Found,
+    Missing,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MovePlan {
+    id: Move,
+    date: NaiveDate,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    failed: bool,
+    html: Need,
+    exports: BTreeMap<ExportFormat, Need>,
+}
+
+impl MovePlan {
+    fn is_done(&self) -> bool {
+        self.html == Need::Done && self.exports.values().all(|need| *need == Need::Done)
+    }
+}
+
+#[derive(Default, Serialize)]
+pub(crate) struct Counts {
+    done: usize,
+    found: usize,
+    missing: usize,
+}
+
+impl Counts {
+    fn add(&mut self, need: Need) {
+        match need {
+            Need::Done => self.done += 1,
+            Need::Found => self.found += 1,
+            Need::Missing => self.missing += 1,
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+pub(crate) struct Totals {
+    moves: usize,
+    failed: usize,
+    html: Counts,
+    exports: BTreeMap<ExportFormat, Counts>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Plan {
+    moves: Vec<MovePlan>,
+    totals: Totals,
+}
+
+impl Plan {
+    pub(crate) fn new<T: RangeBounds<NaiveDate>>(
+        ledger: &Ledger,
+        index: &ExportIndex,
+        formats: &[ExportFormat],
+        range: &T,
+    ) -> Self {
+        let mut totals = Totals::default();
+        let mut moves = Vec::new();
+        for mv in ledger.moves() {
+            let status = match ledger.status(mv) {
+                Some(status) if range.contains(&status.date) => status,
+                _ => continue,
+            };
+            let plan = MovePlan {
+                id: mv,
+                date: status.date,
+                failed: status.failed,
+                html: html_need(mv, status),
+                exports: formats
+                    .iter()
+                    .map(|format| (*format, export_need(ledger, index, *format, status)))
+                    .collect(),
+            };
+            totals.moves += 1;
+            if plan.failed {
+                totals.failed += 1;
+            }
+            totals.html.add(plan.html);
+            for (format, need) in &plan.exports {
+                totals.exports.entry(*format).or_default().add(*need);
+            }
+            moves.push(plan);
+        }
+        Self { moves, totals }
+    }
+
+    pub(crate) fn print(&self) {
+        for line in self.lines() {
+            println!("{}", line);
+        }
+    }
+
+    // The moves that aren't done, and then the totals.
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for plan in self.moves.iter().filter(|plan| !plan.is_done()) {
+            let mut line = format!("{} {:>10}  html: {:7}", plan.date, plan.id, need(plan.html));
+            for (format, need_) in &plan.exports {
+                line.push_str(&format!("  {}: {:7}", format, need(*need_)));
+            }
+            if plan.failed {
+                line.push_str("  (failed)");
+            }
+            lines.push(line.trim_end().to_string());
+        }
+
+        let totals = &self.totals;
+        lines.push(format!(
+            "{} moves, {} of them failed",
+            totals.moves, totals.failed
+        ));
+        lines.push(counts("html", &totals.html));
+        for (format, counts_) in &totals.exports {
+            lines.push(counts(&format.to_string(), counts_));
+        }
+        lines
+    }
+}
+
+fn need(need: Need) -> &'static str {
+    match need {
+        Need::Done => "done",
+        Need::Found => "found",
+        Need::Missing => "missing",
+    }
+}
+
+fn counts(what: &str, counts: &Counts) -> String {
+    format!(
+        "  {:4}: {:5} done, {:5} found (to be recorded), {:5} missing",
+        what, counts.done, counts.found, counts.missing
+    )
+}
+
+// save_html doesn't fetch pages that are already there.
+fn html_need(mv: Move, status: &MoveStatus) -> Need {
+    if status.html.as_deref().is_some_and(Path::exists) {
+        Need::Done
+    } else if Path::new(&ledger::html_filename(mv, status.date)).exists() {
+        Need::Found
+    } else {
+        Need::Missing
+    }
+}
+
+// Like export, if there are at least as many files as moves on that
+// day, we assume they're all there.  Otherwise, if we know when the
+// move started, we can look for its file.
+fn export_need(
+    ledger: &Ledger,
+    index: &ExportIndex,
+    format: ExportFormat,
+    status: &MoveStatus,
+) -> Need {
+    if status.export(format).is_some_and(Path::exists) {
+        return Need::Done;
+    }
+    if index.count_for_date(format, status.date) >= ledger.count_on(status.date) {
+        return Need::Found;
+    }
+    let found = status.start.is_some_and(|start| {
+        index
+            .find(format, status.date, start.hour(), start.minute())
+            .iter()
+            .any(|path| !ledger.is_known_export(path))
+    });
+    if found {
+        Need::Found
+    } else {
+        Need::Missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::move_scraper::DatedMoves,
+        std::{fs, process},
+    };
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn lists_what_each_move_is_missing() {
+        let dir = std::env::temp_dir().join(format!("retrieve-moves-plan-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str| {
+            let path = dir.join(name);
+            fs::write(&path, "").unwrap();
+            path
+        };
+        let html = file("Move_2019_06_01_101.html");
+        let gpx = file("Move_2019_06_01_07_30_12_Running_101.gpx");
+        // Not yet known to belong to 102, but there are as many GPX
+        // files as moves that day.
+        file("Move_2019_06_01_09_00_00_Cycling.gpx");
+        // 104's, by its start time.
+        file("Move_2019_06_15_18_00_00_Running.fit");
+
+        let mut ledger = Ledger::default();
+        let mv = |id: &str| id.parse::<Move>().unwrap();
+        ledger.merge(&[
+            DatedMoves {
+                date: ymd(2019, 6, 1),
+                moves: vec![mv("101"), mv("102")],
+            },
+            DatedMoves {
+                date: ymd(2019, 6, 15),
+                moves: vec![mv("103"), mv("104")],
+            },
+            DatedMoves {
+                date: ymd(2019, 7, 2),
+                moves: vec![mv("105")],
+            },
+        ]);
+        let status = ledger.status_mut(mv("101")).unwrap();
+        status.html = Some(html);
+        status.exports.insert(ExportFormat::Gpx, gpx);
+        ledger.status_mut(mv("103")).unwrap().failed = true;
+        ledger.status_mut(mv("104")).unwrap().start = ymd(2019, 6, 15).and_hms_opt(18, 0, 0);
+
+        let index = ExportIndex::new(&dir).unwrap();
+        let plan = Plan::new(
+            &ledger,
+            &index,
+            &[ExportFormat::Gpx, ExportFormat::Fit],
+            &(ymd(2019, 6, 1)..=ymd(2019, 6, 30)),
+        );
+        assert_eq!(
+            plan.lines(),
+            vec![
+                "2019-06-01        101  html: done     gpx: done     fit: missing",
+                "2019-06-01        102  html: missing  gpx: found    fit: missing",
+                "2019-06-15        103  html: missing  gpx: missing  fit: missing  (failed)",
+                "2019-06-15        104  html: missing  gpx: missing  fit: found",
+                "4 moves, 1 of them failed",
+                "  html:     1 done,     0 found (to be recorded),     3 missing",
+                "  gpx :     1 done,     1 found (to be recorded),     2 missing",
+                "  fit :     0 done,     1 found (to be recorded),     3 missing",
+            ]
+        );
+
+        // A move that's done isn't listed, only counted.
+        let plan = Plan::new(
+            &ledger,
+            &index,
+            &[ExportFormat::Gpx],
+            &(ymd(2019, 6, 1)..=ymd(2019, 6, 1)),
+        );
+        assert_eq!(
+            plan.lines(),
+            vec![
+                "2019-06-01        102  html: missing  gpx: found",
+                "2 moves, 0 of them failed",
+                "  html:     1 done,     0 found (to be recorded),     1 missing",
+                "  gpx :     1 done,     1 found (to be recorded),     0 missing",
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
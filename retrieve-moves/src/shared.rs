@@ -0,0 +1,114 @@
+use {
+    crate::{export_index::ExportIndex, ledger::Ledger, logging::Progress},
+    anyhow::Result,
+    std::{
+        path::{Path, PathBuf},
+        sync::{Mutex, MutexGuard},
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+// What all of the WebDriver sessions share with --sessions: the
+// ledger, the index of what's been exported (to the main download
+// directory, where each session's downloads end up), where failures
+// are captured and the rate limit.  The locks are only held for as
+// long as it takes to look something up or record it, never while
+// waiting on the browser.  When both are needed, the ledger is locked
+// first.
+
+pub(crate) struct Shared {
+    ledger: Mutex<Ledger>,
+    export_index: Mutex<ExportIndex>,
+    pub(crate) download_dir: PathBuf,
+    pub(crate) failures_dir: PathBuf,
+    pub(crate) rate_limit: RateLimit,
+    pub(crate) progress: Progress,
+}
+
+impl Shared {
+    // Any session_dirs that are there already are indexed too, for
+    // downloads that a previous run didn't get to move into
+    // download_dir.
+    pub(crate) fn new(
+        ledger: Ledger,
+        download_dir: &Path,
+        session_dirs: &[PathBuf],
+        failures_dir: &Path,
+        min_interval: Duration,
+        progress: Progress,
+    ) -> Result<Self> {
+        let mut export_index = ExportIndex::new(download_dir)?;
+        for dir in session_dirs
+            .iter()
+            .filter(|dir| dir.as_path() != download_dir && dir.is_dir())
+        {
+            export_index.add_dir(dir)?;
+        }
+        Ok(Self {
+            ledger: Mutex::new(ledger),
+            export_index: Mutex::new(export_index),
+            download_dir: download_dir.to_path_buf(),
+            failures_dir: failures_dir.to_path_buf(),
+            rate_limit: RateLimit::new(min_interval),
+            progress,
+        })
+    }
+
+    // A session that panicked while holding a lock can't have left
+    // things any worse than a crash would, and the ledger is saved
+    // whole, so a poisoned lock is still usable.
+    pub(crate) fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn export_index(&self) -> MutexGuard<'_, ExportIndex> {
+        self.export_index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// To be polite to Movescount, no matter how many sessions there are,
+// page loads and export clicks are at least min_interval apart.
+pub(crate) struct RateLimit {
+    min_interval: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    pub(crate) fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            next: Mutex::new(None),
+        }
+    }
+
+    // Reserves the next slot and then sleeps until it comes around.
+    pub(crate) fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + self.min_interval);
+            slot
+        };
+        thread::sleep(slot.saturating_duration_since(Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_spaces_out_every_thread() {
+        let rate_limit = RateLimit::new(Duration::from_millis(50));
+        let start = Instant::now();
+        thread::scope(|scope| {
+            for _ in 0..3 {
+                scope.spawn(|| rate_limit.wait());
+            }
+        });
+        // The first goes straight away.
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,153 @@
+use {
+    crate::{
+        export_format::ExportFormat, export_index::ExportIndex, ledger::Ledger, move_scraper::Move,
+    },
+    anyhow::{anyhow, bail, Result},
+    std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        io::{BufReader, Read},
+        path::{Path, PathBuf},
+    },
+    xml::reader::{EventReader, XmlEvent},
+};
+
+// Some long moves used to be only partly downloaded, and all that was
+// checked was how many files there were for each date.  The "verify"
+// subcommand reads every GPX file, the ones in --download-dir and the
+// ones the ledger knows of, and checks that it's well-formed XML that
+// ends with </gpx>, which a file that was cut off isn't.
+//
+// A track that's complete as a file but still missing some of the
+// move would need comparing with the duration and distance on the
+// move's page, which move_page.rs doesn't read yet.
+//
+// A file that fails is renamed to end with .bad (so it's kept, but no
+// longer looks like an export) and forgotten by the ledger, so that
+// the next run with --export exports it again.
+
+pub(crate) fn verify(ledger: &mut Ledger, download_dir: &Path, dry_run: bool) -> Result<()> {
+    let files = gpx_files(ledger, download_dir)?;
+    let mut bad = Vec::new();
+    for (path, mv) in &files {
+        if let Err(e) = read_gpx(path) {
+            println!("{}: {:#}", path.display(), e);
+            bad.push((path, *mv));
+        }
+    }
+    println!(
+        "{} GPX files, {} to be exported again",
+        files.len(),
+        bad.len()
+    );
+    if dry_run || bad.is_empty() {
+        return Ok(());
+    }
+
+    for (path, mv) in bad {
+        if path.exists() {
+            let mut renamed = path.clone().into_os_string();
+            renamed.push(".bad");
+            fs::rename(path, &renamed)?;
+        }
+        if let Some(status) = mv.and_then(|mv| ledger.status_mut(mv)) {
+            status.exports.remove(&ExportFormat::Gpx);
+            status.failed = false;
+            status.attempts = 0;
+        }
+    }
+    ledger.save()
+}
+
+// Each GPX file once, with its move if the ledger knows it.  The
+// index and the ledger may spell the same file differently (e.g.,
+// ./dl/x.gpx and dl/x.gpx), so the paths are canonicalized.  A file
+// that's gone keeps the ledger's path, and fails when it's read.
+fn gpx_files(ledger: &Ledger, download_dir: &Path) -> Result<BTreeMap<PathBuf, Option<Move>>> {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut files = BTreeMap::new();
+    for path in ExportIndex::new(download_dir)?.paths(ExportFormat::Gpx) {
+        files.insert(canonical(path), None);
+    }
+    for mv in ledger.moves() {
+        if let Some(path) = ledger.status(mv).and_then(|s| s.export(ExportFormat::Gpx)) {
+            files.insert(canonical(path), Some(mv));
+        }
+    }
+    Ok(files)
+}
+
+fn read_gpx(path: &Path) -> Result<()> {
+    let file = File::open(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    check_gpx(BufReader::new(file))
+}
+
+// Fails if the GPX isn't well-formed, which is what a file that was
+// cut off looks like.
+fn check_gpx(gpx: impl Read) -> Result<()> {
+    let mut closed = false;
+    for event in EventReader::new(gpx) {
+        if let XmlEvent::EndElement { name } =
+            event.map_err(|e| anyhow!("not well-formed: {}", e))?
+        {
+            closed = name.local_name == "gpx";
+        }
+    }
+    if !closed {
+        bail!("no closing </gpx>");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::move_scraper::DatedMoves, chrono::NaiveDate, std::process};
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="Movescount" xmlns="http://www.topografix.com/GPX/1/1">
+<metadata><time>2019-06-01T09:00:00Z</time></metadata>
+<trk><trkseg>
+<trkpt lat="46.8" lon="-121.76"><ele>1200</ele><time>2019-06-01T07:30:12Z</time></trkpt>
+<trkpt lat="46.9" lon="-121.76"><ele>1210</ele><time>2019-06-01T08:32:12Z</time></trkpt>
+</trkseg></trk>
+</gpx>
+"#;
+
+    #[test]
+    fn a_cut_off_file_isnt_well_formed() {
+        check_gpx(GPX.as_bytes()).unwrap();
+        let cut = &GPX[..GPX.len() / 2];
+        let e = check_gpx(cut.as_bytes()).unwrap_err();
+        assert!(e.to_string().starts_with("not well-formed"), "{}", e);
+        assert!(check_gpx(GPX.replace("</gpx>", "").as_bytes()).is_err());
+        assert_eq!(
+            check_gpx("<kml></kml>".as_bytes()).unwrap_err().to_string(),
+            "no closing </gpx>"
+        );
+    }
+
+    #[test]
+    fn a_file_is_only_counted_once() {
+        let dir = std::env::temp_dir().join(format!("retrieve-moves-verify-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let name = "Move_2019_06_01_07_30_12_Running.gpx";
+        fs::write(dir.join(name), GPX).unwrap();
+        let mv = "101".parse().unwrap();
+        let mut ledger = Ledger::default();
+        ledger.merge(&[DatedMoves {
+            date: NaiveDate::from_ymd_opt(2019, 6, 1).unwrap(),
+            moves: vec![mv],
+        }]);
+        ledger.status_mut(mv).unwrap().exports.insert(
+            ExportFormat::Gpx,
+            dir.join("..").join(dir.file_name().unwrap()).join(name),
+        );
+
+        let files = gpx_files(&ledger, &dir).unwrap();
+        assert_eq!(
+            files.into_iter().collect::<Vec<_>>(),
+            vec![(fs::canonicalize(dir.join(name)).unwrap(), Some(mv))]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}